@@ -0,0 +1,142 @@
+//! Measures the GenBank reader on a bacterial genome and a set of eukaryotic
+//! chromosomes. Real files can be given with `ANNOTS_BENCH_BACTERIAL` and
+//! `ANNOTS_BENCH_EUKARYOTIC` (plain GenBank); otherwise inputs of similar
+//! shape are generated.
+
+use annots::gbk;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+
+/// A GenBank record of `length` bases with `genes` annotated genes, each a
+/// CDS of `exons` exons with a wrapped `/translation`.
+fn synthetic_record(id: &str, length: usize, genes: usize, exons: usize) -> String {
+    let mut text = String::new();
+    writeln!(
+        text,
+        "LOCUS       {:<16}{:>12} bp    DNA     linear   CON 01-JAN-2020",
+        id, length
+    )
+    .unwrap();
+    writeln!(
+        text,
+        "DEFINITION  Synthetic sequence {} for benchmarks.",
+        id
+    )
+    .unwrap();
+    writeln!(text, "ACCESSION   {}", id).unwrap();
+    writeln!(text, "VERSION     {}.1", id).unwrap();
+    writeln!(text, "FEATURES             Location/Qualifiers").unwrap();
+    writeln!(text, "     source          1..{}", length).unwrap();
+    writeln!(
+        text,
+        "                     /organism=\"Synthetic organism\""
+    )
+    .unwrap();
+    let span = length / genes;
+    for gene in 0..genes {
+        let start = gene * span + 1;
+        let exon = (span - 10) / (2 * exons);
+        let parts: Vec<String> = (0..exons)
+            .map(|e| {
+                let from = start + 2 * e * exon;
+                format!("{}..{}", from, from + exon - 1)
+            })
+            .collect();
+        let location = if exons == 1 {
+            parts[0].clone()
+        } else {
+            format!("join({})", parts.join(","))
+        };
+        let tag = format!("{}_{:05}", id, gene);
+        writeln!(text, "     gene            {}", location).unwrap();
+        writeln!(text, "                     /locus_tag=\"{}\"", tag).unwrap();
+        writeln!(text, "     CDS             {}", location).unwrap();
+        writeln!(text, "                     /locus_tag=\"{}\"", tag).unwrap();
+        writeln!(
+            text,
+            "                     /product=\"hypothetical protein\""
+        )
+        .unwrap();
+        writeln!(
+            text,
+            "                     /note=\"synthetic coding sequence used to measure"
+        )
+        .unwrap();
+        writeln!(
+            text,
+            "                     the speed of the flat file reader\""
+        )
+        .unwrap();
+        let protein = "MSTNPKPQRKTKRNTNRRPQDVKFPGG".repeat(exon * exons / 81 + 1);
+        let mut translation = format!("/translation=\"{}\"", protein);
+        while !translation.is_empty() {
+            let end = translation.len().min(58);
+            let rest = translation.split_off(end);
+            writeln!(text, "                     {}", translation).unwrap();
+            translation = rest;
+        }
+    }
+    writeln!(text, "ORIGIN").unwrap();
+    let bases = b"acgtgcatgcaatcgcgtatatcggatccgatcgtagcatgcatcgatcgatcgcgatcg";
+    for line_start in (0..length).step_by(60) {
+        write!(text, "{:>9}", line_start + 1).unwrap();
+        for block in (line_start..(line_start + 60).min(length)).step_by(10) {
+            let end = (block + 10).min(length);
+            let chunk: String = (block..end)
+                .map(|i| bases[i % bases.len()] as char)
+                .collect();
+            write!(text, " {}", chunk).unwrap();
+        }
+        writeln!(text).unwrap();
+    }
+    writeln!(text, "//").unwrap();
+    text
+}
+
+fn input(variable: &str, generate: impl FnOnce() -> String) -> Vec<u8> {
+    match env::var_os(variable) {
+        Some(path) => fs::read(&path).expect("benchmark input is readable"),
+        None => generate().into_bytes(),
+    }
+}
+
+fn readers(c: &mut Criterion) {
+    let inputs = [
+        (
+            "bacterial",
+            input("ANNOTS_BENCH_BACTERIAL", || {
+                synthetic_record("NC_BACT01", 5_000_000, 4_500, 1)
+            }),
+        ),
+        (
+            "eukaryotic",
+            input("ANNOTS_BENCH_EUKARYOTIC", || {
+                (1..=8)
+                    .map(|n| synthetic_record(&format!("NC_EUK{:03}", n), 4_000_000, 400, 8))
+                    .collect()
+            }),
+        ),
+    ];
+    for (name, data) in inputs.iter() {
+        let mut group = c.benchmark_group(format!("genbank/{}", name));
+        group.sample_size(10);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function("read", |b| {
+            b.iter(|| {
+                let reader = gbk::Reader::new(&data[..]);
+                let mut bases = 0;
+                for record in reader.records() {
+                    let record = record.unwrap();
+                    bases += record.sequence().len() + record.features().len();
+                }
+                bases
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, readers);
+criterion_main!(benches);
@@ -0,0 +1,281 @@
+use crate::sink::AtomicFile;
+use anyhow::Context;
+use flate2::read::DeflateDecoder;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes of the gzip header of a BGZF block, up to the block size field.
+const HEADER: usize = 18;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Path of the block index kept next to `path`.
+pub fn gzi_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gzi");
+    PathBuf::from(name)
+}
+
+/// Read the header of the block at the current position, returning its
+/// total size, or `None` at the end of the file.
+fn block_size<R: Read>(reader: &mut R, header: &mut [u8; HEADER]) -> io::Result<Option<usize>> {
+    let mut filled = 0;
+    while filled < HEADER {
+        match reader.read(&mut header[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(invalid("Truncated BGZF block header")),
+            n => filled += n,
+        }
+    }
+    let bgzf = header[..4] == [0x1f, 0x8b, 0x08, 0x04] && &header[12..14] == b"BC";
+    if !bgzf {
+        return Err(invalid(
+            "Not a BGZF block; compress with bgzip or --compress bgzf",
+        ));
+    }
+    Ok(Some(
+        u16::from_le_bytes([header[16], header[17]]) as usize + 1,
+    ))
+}
+
+/// Compressed and uncompressed offsets of every BGZF block, as in the
+/// `.gzi` files written by `bgzip -r`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Gzi {
+    /// Block starts, beginning with `(0, 0)`.
+    blocks: Vec<(u64, u64)>,
+}
+
+impl Gzi {
+    /// Walk the block headers of a BGZF stream, without inflating.
+    pub fn build<R: Read>(mut reader: R) -> io::Result<Gzi> {
+        let mut blocks = Vec::new();
+        let (mut compressed, mut uncompressed) = (0, 0);
+        let mut header = [0; HEADER];
+        let mut rest = Vec::new();
+        while let Some(size) = block_size(&mut reader, &mut header)? {
+            if size < HEADER + 8 {
+                return Err(invalid("BGZF block is too small"));
+            }
+            rest.resize(size - HEADER, 0);
+            reader.read_exact(&mut rest)?;
+            let length = &rest[rest.len() - 4..];
+            blocks.push((compressed, uncompressed));
+            compressed += size as u64;
+            uncompressed += u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as u64;
+        }
+        Ok(Gzi { blocks })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Gzi> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        Gzi::build(io::BufReader::new(file)).with_context(|| format!("Failed to index {:?}", path))
+    }
+
+    /// Read a `.gzi`: a count, then compressed and uncompressed offset pairs
+    /// for every block after the first, all little-endian `u64`.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Gzi> {
+        let mut number = [0; 8];
+        let mut next = |reader: &mut R| -> io::Result<u64> {
+            reader.read_exact(&mut number)?;
+            Ok(u64::from_le_bytes(number))
+        };
+        let count = next(&mut reader)?;
+        let mut blocks = vec![(0, 0)];
+        for _ in 0..count {
+            blocks.push((next(&mut reader)?, next(&mut reader)?));
+        }
+        Ok(Gzi { blocks })
+    }
+
+    /// Read the `.gzi` kept next to `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Gzi> {
+        let gzi = gzi_path(path.as_ref());
+        let file = fs::File::open(&gzi).with_context(|| {
+            format!(
+                "Failed to open {:?}; run annots index {:?}",
+                gzi,
+                path.as_ref()
+            )
+        })?;
+        Gzi::read(io::BufReader::new(file)).with_context(|| format!("Failed to read {:?}", gzi))
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let blocks = self.blocks.get(1..).unwrap_or_default();
+        out.write_all(&(blocks.len() as u64).to_le_bytes())?;
+        for (compressed, uncompressed) in blocks {
+            out.write_all(&compressed.to_le_bytes())?;
+            out.write_all(&uncompressed.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Write the `.gzi` next to `path`, replacing any older one.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = AtomicFile::create(gzi_path(path.as_ref()))?;
+        self.write(&mut file)?;
+        file.commit()
+    }
+
+    /// The start of the block holding uncompressed byte `offset`.
+    fn block(&self, offset: u64) -> (u64, u64) {
+        let i = self.blocks.partition_point(|&(_, start)| start <= offset);
+        self.blocks
+            .get(i.wrapping_sub(1))
+            .copied()
+            .unwrap_or((0, 0))
+    }
+
+    /// BGZF virtual offset of uncompressed byte `offset`: the compressed
+    /// offset of its block shifted left by 16, plus its offset within the
+    /// block.
+    pub fn virtual_offset(&self, offset: u64) -> u64 {
+        let (compressed, start) = self.block(offset);
+        (compressed << 16) | (offset - start)
+    }
+}
+
+/// Reads a BGZF file as its uncompressed bytes, seeking to uncompressed
+/// offsets through their virtual offsets.
+pub struct Reader<R> {
+    inner: R,
+    gzi: Gzi,
+    /// The inflated current block.
+    block: Vec<u8>,
+    position: usize,
+    /// Compressed offset of the block after the current one.
+    next: u64,
+    header: [u8; HEADER],
+    compressed: Vec<u8>,
+}
+
+impl Reader<io::BufReader<fs::File>> {
+    /// Open `path` with the `.gzi` kept next to it.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let gzi = Gzi::load(path)?;
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        Ok(Reader::new(io::BufReader::new(file), gzi))
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    pub fn new(inner: R, gzi: Gzi) -> Self {
+        Reader {
+            inner,
+            gzi,
+            block: Vec::new(),
+            position: 0,
+            next: 0,
+            header: [0; HEADER],
+            compressed: Vec::new(),
+        }
+    }
+
+    /// Move to a virtual offset, as found in the `.gzi` or a BAI-style index.
+    pub fn seek_virtual(&mut self, offset: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset >> 16))?;
+        self.next = offset >> 16;
+        self.read_block()?;
+        let within = (offset & 0xffff) as usize;
+        if within > self.block.len() {
+            return Err(invalid("Virtual offset lies beyond its block"));
+        }
+        self.position = within;
+        Ok(())
+    }
+
+    /// Inflate the block at `self.next`, leaving an empty block at the end
+    /// of the file.
+    fn read_block(&mut self) -> io::Result<()> {
+        self.block.clear();
+        self.position = 0;
+        let size = match block_size(&mut self.inner, &mut self.header)? {
+            Some(size) if size >= HEADER + 8 => size,
+            Some(_) => return Err(invalid("BGZF block is too small")),
+            None => return Ok(()),
+        };
+        self.compressed.resize(size - HEADER, 0);
+        self.inner.read_exact(&mut self.compressed)?;
+        let deflated = &self.compressed[..self.compressed.len() - 8];
+        DeflateDecoder::new(deflated).read_to_end(&mut self.block)?;
+        self.next += size as u64;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // skip the empty EOF block and any other empty ones
+        while self.position == self.block.len() {
+            let at = self.next;
+            self.read_block()?;
+            if self.block.is_empty() && self.next == at {
+                return Ok(0);
+            }
+        }
+        let available = &self.block[self.position..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for Reader<R> {
+    /// Only seeking to an uncompressed offset from the start is supported.
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match position {
+            SeekFrom::Start(offset) => {
+                self.seek_virtual(self.gzi.virtual_offset(offset))?;
+                Ok(offset)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "BGZF files can only be seeked from the start",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::BgzfWriter;
+    use std::io::Cursor;
+
+    #[test]
+    fn seeks_to_uncompressed_offsets_across_blocks() {
+        let plain: Vec<u8> = (0..200_000u32)
+            .map(|i| b"acgtn\n"[(i * 7 % 13 % 6) as usize])
+            .collect();
+        let mut writer = BgzfWriter::new(Vec::new());
+        writer.write_all(&plain).unwrap();
+        let compressed = writer.finish().unwrap();
+        let gzi = Gzi::build(&compressed[..]).unwrap();
+        let mut saved = Vec::new();
+        gzi.write(&mut saved).unwrap();
+        assert_eq!(Gzi::read(&saved[..]).unwrap(), gzi);
+        let mut reader = Reader::new(Cursor::new(compressed), gzi);
+        for offset in [
+            0,
+            1,
+            0xff00 - 1,
+            0xff00,
+            0xff00 + 1,
+            150_000,
+            199_999,
+            200_000,
+        ] {
+            reader.seek(SeekFrom::Start(offset)).unwrap();
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, &plain[offset as usize..], "from offset {}", offset);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::Context;
+use flate2::write::DeflateEncoder;
+use flate2::Crc;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Compression of an input, recognised by its magic bytes, or of an output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    /// Blocked gzip, as written by bgzip and samtools.
+    Bgzf,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Bgzf => "bgzf",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Compression {
+    pub fn detect(head: &[u8]) -> Compression {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            // FEXTRA set and a BC subfield holding the block size
+            let bgzf = head.len() >= 16 && head[3] & 4 != 0 && &head[12..14] == b"BC";
+            if bgzf {
+                Compression::Bgzf
+            } else {
+                Compression::Gzip
+            }
+        } else if head.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wrap `reader` in the decoder its magic bytes call for. Concatenated
+/// members and frames are all read, so BGZF and `cat a.gz b.gz` work.
+pub fn decompress<R: BufRead + Send + 'static>(
+    mut reader: R,
+) -> io::Result<Box<dyn BufRead + Send>> {
+    let compression = Compression::detect(reader.fill_buf()?);
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip | Compression::Bgzf => Box::new(io::BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        )),
+        Compression::Bzip2 => Box::new(io::BufReader::new(bzip2::bufread::MultiBzDecoder::new(
+            reader,
+        ))),
+        Compression::Xz => Box::new(io::BufReader::new(
+            xz2::bufread::XzDecoder::new_multi_decoder(reader),
+        )),
+        Compression::Zstd => Box::new(io::BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// Open `path` for reading, decompressing it if needed.
+pub fn open<P: AsRef<Path> + fmt::Debug>(path: P) -> anyhow::Result<Box<dyn BufRead + Send>> {
+    let file = fs::File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+    decompress(io::BufReader::new(file)).with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// Compression of the file at `path`, from its magic bytes.
+pub fn sniff<P: AsRef<Path> + fmt::Debug>(path: P) -> anyhow::Result<Compression> {
+    let file = fs::File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut head = Vec::with_capacity(16);
+    file.take(16)
+        .read_to_end(&mut head)
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(Compression::detect(&head))
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "plain" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "bgzf" | "bgzip" | "bgz" => Ok(Compression::Bgzf),
+            "bzip2" | "bz2" => Ok(Compression::Bzip2),
+            "xz" => Ok(Compression::Xz),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression {:?}", s)),
+        }
+    }
+}
+
+impl Compression {
+    /// Compression suggested by the extension of an output path, e.g.
+    /// `out.faa.gz`.
+    pub fn from_path(path: &Path) -> Option<Compression> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "bgz" => Some(Compression::Bgzf),
+            "bz2" => Some(Compression::Bzip2),
+            "xz" => Some(Compression::Xz),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Extension appended to file names written with this compression.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip | Compression::Bgzf => ".gz",
+            Compression::Bzip2 => ".bz2",
+            Compression::Xz => ".xz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// Largest amount of data put in one BGZF block, as bgzip does.
+const BGZF_BLOCK: usize = 0xff00;
+
+/// Empty block marking the end of a BGZF file.
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Writes blocked gzip that plain gzip readers and htslib can both read.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(inner: W) -> Self {
+        BgzfWriter {
+            inner,
+            buffer: Vec::with_capacity(BGZF_BLOCK),
+        }
+    }
+
+    fn write_block(&mut self, end: usize) -> io::Result<()> {
+        let data = &self.buffer[..end];
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data)?;
+        let deflated = encoder.finish()?;
+        let mut crc = Crc::new();
+        crc.update(data);
+        // block size minus one, counting the 18 byte header and 8 byte footer
+        let block_size = (deflated.len() + 25) as u16;
+        let mut header = [
+            0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C',
+            0x02, 0x00, 0x00, 0x00,
+        ];
+        header[16..18].copy_from_slice(&block_size.to_le_bytes());
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&deflated)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.buffer.drain(..end);
+        Ok(())
+    }
+
+    /// Write the last block and the EOF marker, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.buffer.is_empty() {
+            self.write_block(self.buffer.len())?;
+        }
+        self.inner.write_all(&BGZF_EOF)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while self.buffer.len() >= BGZF_BLOCK {
+            self.write_block(BGZF_BLOCK)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"LOCUS       T1  6 bp    DNA     linear\n//\n";
+
+    fn compressed(compression: Compression, data: &[u8]) -> Vec<u8> {
+        match compression {
+            Compression::None => data.to_vec(),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Bgzf => {
+                let mut writer = BgzfWriter::new(Vec::new());
+                writer.write_all(data).unwrap();
+                writer.finish().unwrap()
+            }
+            Compression::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+        }
+    }
+
+    const ALL: [Compression; 6] = [
+        Compression::None,
+        Compression::Gzip,
+        Compression::Bgzf,
+        Compression::Bzip2,
+        Compression::Xz,
+        Compression::Zstd,
+    ];
+
+    #[test]
+    fn recognises_magic_bytes() {
+        for compression in ALL {
+            assert_eq!(
+                Compression::detect(&compressed(compression, TEXT)),
+                compression
+            );
+        }
+        assert_eq!(Compression::detect(&[0x1f]), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+
+    #[test]
+    fn reads_every_member_back() {
+        for compression in ALL {
+            let mut data = compressed(compression, TEXT);
+            data.extend(compressed(compression, TEXT));
+            let mut text = Vec::new();
+            decompress(io::Cursor::new(data))
+                .unwrap()
+                .read_to_end(&mut text)
+                .unwrap();
+            assert_eq!(text, [TEXT, TEXT].concat(), "{}", compression);
+        }
+    }
+
+    #[test]
+    fn sniffs_and_opens_files() {
+        let path = std::env::temp_dir().join(format!("annots-sniff-{}.gz", std::process::id()));
+        fs::write(&path, compressed(Compression::Bzip2, TEXT)).unwrap();
+        assert_eq!(sniff(&path).unwrap(), Compression::Bzip2);
+        let mut text = Vec::new();
+        open(&path).unwrap().read_to_end(&mut text).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(text, TEXT);
+        let error = sniff(&path).unwrap_err();
+        assert!(error.to_string().starts_with("Failed to open"));
+    }
+
+    #[test]
+    fn names_compressions() {
+        for compression in ALL {
+            assert_eq!(compression.to_string().parse(), Ok(compression));
+        }
+        assert_eq!("bgzip".parse(), Ok(Compression::Bgzf));
+        assert!("lz4".parse::<Compression>().is_err());
+        assert_eq!(
+            Compression::from_path(Path::new("out.faa.zst")),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_path(Path::new("out.faa")), None);
+        assert_eq!(Compression::Bgzf.extension(), ".gz");
+    }
+}
@@ -0,0 +1,553 @@
+use crate::filter::Filter;
+use crate::flank::Flanks;
+use crate::header::Template;
+use crate::ids::{IdChain, UniqueIds};
+use crate::intergenic::{intergenic_regions, IntergenicOptions};
+use crate::ncbi::{ncbi_header, NcbiFile};
+use crate::order::SortOrder;
+use crate::policy::Policies;
+use crate::record::{Feature, ProteinSource, Record};
+use crate::translate::StopPolicy;
+use crate::verify::{verify_cds, Verification};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Kind of fasta written from each record.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    /// The whole record sequence.
+    Fna,
+    /// Protein sequences of the CDS.
+    Faa,
+    /// Nucleotide sequences of the selected features.
+    Ffn,
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Output::Fna => "fna",
+            Output::Faa => "faa",
+            Output::Ffn => "ffn",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fna" | "fasta" => Ok(Output::Fna),
+            "faa" => Ok(Output::Faa),
+            "ffn" => Ok(Output::Ffn),
+            _ => Err(format!("unknown output format {:?}", s)),
+        }
+    }
+}
+
+/// Everything that decides which sequences are written and how they are named.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub header: Option<Template>,
+    pub ncbi: bool,
+    pub id_chain: IdChain,
+    pub sort: SortOrder,
+    pub policies: Policies,
+    /// Feature keys written as ffn, or every key when empty.
+    pub kinds: Vec<String>,
+    /// Only features matching this are written as faa or ffn.
+    pub filter: Option<Filter>,
+    pub flanks: Flanks,
+    pub flanks_only: bool,
+    pub intergenic: Option<IntergenicOptions>,
+    /// Prefer `/translation` over translating the CDS.
+    pub annotated: bool,
+    pub stops: StopPolicy,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            header: None,
+            ncbi: false,
+            id_chain: IdChain::default(),
+            sort: SortOrder::default(),
+            policies: Policies::default(),
+            kinds: vec!["CDS".to_string()],
+            filter: None,
+            flanks: Flanks::default(),
+            flanks_only: false,
+            intergenic: None,
+            annotated: false,
+            stops: StopPolicy::Truncate,
+        }
+    }
+}
+
+/// One fasta record ready to be written.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// Unique id of the feature, or the record id for fna.
+    pub id: String,
+    pub header: String,
+    pub sequence: Vec<u8>,
+    /// Where a protein came from, for faa.
+    pub source: Option<ProteinSource>,
+}
+
+/// A sequence cut from a feature, or one of its flanks.
+#[derive(Clone, Debug)]
+struct Piece {
+    /// `upstream` or `downstream` for a flank written on its own.
+    side: Option<&'static str>,
+    location: Option<String>,
+    sequence: Vec<u8>,
+}
+
+/// A selected feature with its sequences, still to be named.
+#[derive(Clone, Debug)]
+struct Item {
+    /// Index of the feature in its record.
+    feature: usize,
+    base_id: String,
+    flags: String,
+    pieces: Vec<Piece>,
+    source: Option<ProteinSource>,
+}
+
+/// Sequences cut and translated from one record, waiting for the ids that
+/// depend on every record before it.
+#[derive(Clone, Debug, Default)]
+pub struct Prepared {
+    items: Vec<Item>,
+    skipped: Vec<String>,
+}
+
+impl Prepared {
+    /// A message for each feature skipped because its location is incomplete.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+}
+
+/// The part of a `Converter` that keeps no running state: selecting
+/// features, cutting out their sequences and translating them. It can be
+/// shared between worker threads.
+#[derive(Clone, Debug)]
+pub struct Preparer {
+    output: Output,
+    options: Options,
+}
+
+impl Preparer {
+    pub fn prepare(&self, record: &Record) -> io::Result<Prepared> {
+        let mut skipped = Vec::new();
+        let items = match self.output {
+            Output::Fna => Vec::new(),
+            Output::Faa => self.faa(record, &mut skipped)?,
+            Output::Ffn => self.ffn(record, &mut skipped)?,
+        };
+        Ok(Prepared { items, skipped })
+    }
+
+    fn selects(&self, feature: &Feature) -> bool {
+        match &self.options.filter {
+            Some(filter) => filter.matches(feature),
+            None => true,
+        }
+    }
+
+    fn fna(&self, record: &Record) -> Entry {
+        let header = match &self.options.header {
+            Some(template) => template.render(record, None),
+            None => format!("{}_{:?}", record.id(), record.length()),
+        };
+        Entry {
+            id: record.id().to_string(),
+            header,
+            sequence: record.sequence().to_vec(),
+            source: None,
+        }
+    }
+
+    fn faa(&self, record: &Record, skipped: &mut Vec<String>) -> io::Result<Vec<Item>> {
+        let mut items = Vec::new();
+        for (index, feature) in record.features().iter().enumerate() {
+            if feature.kind != "CDS" || !self.selects(feature) {
+                continue;
+            }
+            let flags = match self.options.policies.flags(feature) {
+                Some(flags) => flags,
+                None => continue,
+            };
+            if feature.location.incomplete {
+                let id = self.options.id_chain.base_id(record, index);
+                skipped.push(incomplete(record, feature, &id));
+                continue;
+            }
+            let (prot_seq, source) = feature.protein(record.sequence(), self.options.annotated)?;
+            let prot_seq = match source {
+                ProteinSource::Annotated => prot_seq,
+                ProteinSource::Translated => self.options.stops.apply(&prot_seq),
+            };
+            items.push(Item {
+                feature: index,
+                base_id: self.options.id_chain.base_id(record, index),
+                flags,
+                pieces: vec![Piece {
+                    side: None,
+                    location: None,
+                    sequence: prot_seq.into_bytes(),
+                }],
+                source: Some(source),
+            });
+        }
+        Ok(items)
+    }
+
+    fn ffn(&self, record: &Record, skipped: &mut Vec<String>) -> io::Result<Vec<Item>> {
+        let options = &self.options;
+        let sequence = record.sequence();
+        let length = sequence.len();
+        let circular = record.is_circular();
+        let mut items = Vec::new();
+        for (index, feature) in record.features().iter().enumerate() {
+            let kind = options.kinds.is_empty() || options.kinds.contains(&feature.kind);
+            if !kind || !self.selects(feature) {
+                continue;
+            }
+            let flags = match options.policies.flags(feature) {
+                Some(flags) => flags,
+                None => continue,
+            };
+            if feature.location.incomplete {
+                let id = options.id_chain.base_id(record, index);
+                skipped.push(incomplete(record, feature, &id));
+                continue;
+            }
+            let mut base_id = options.id_chain.base_id(record, index);
+            if options.kinds.len() > 1 {
+                base_id = format!("{}_{}", base_id, feature.kind);
+            }
+            let pieces = if options.intergenic.is_some() {
+                // the gaps are cut once the features are named and sorted
+                Vec::new()
+            } else if options.flanks.is_empty() {
+                vec![Piece {
+                    side: None,
+                    location: None,
+                    sequence: feature.location.extract(sequence)?,
+                }]
+            } else if options.flanks_only {
+                let windows = [
+                    (
+                        "upstream",
+                        options
+                            .flanks
+                            .upstream_of(&feature.location, length, circular),
+                    ),
+                    (
+                        "downstream",
+                        options
+                            .flanks
+                            .downstream_of(&feature.location, length, circular),
+                    ),
+                ];
+                let mut pieces = Vec::new();
+                for (side, window) in windows.iter().filter(|(_, w)| !w.is_empty()) {
+                    pieces.push(Piece {
+                        side: Some(side),
+                        location: Some(window.to_string()),
+                        sequence: window.extract(sequence)?,
+                    });
+                }
+                pieces
+            } else {
+                let extended = options.flanks.extend(&feature.location, length, circular);
+                vec![Piece {
+                    side: None,
+                    location: Some(extended.to_string()),
+                    sequence: extended.extract(sequence)?,
+                }]
+            };
+            items.push(Item {
+                feature: index,
+                base_id,
+                flags,
+                pieces,
+                source: None,
+            });
+        }
+        Ok(items)
+    }
+}
+
+fn incomplete(record: &Record, feature: &Feature, id: &str) -> String {
+    format!(
+        "Skipped the {} {} in {}: part of its location lies in another record or between two bases",
+        feature.kind,
+        id,
+        record.id()
+    )
+}
+
+/// Ids handed out and NCBI indices counted so far for one output.
+#[derive(Default)]
+struct Scope {
+    ids: UniqueIds,
+    index: usize,
+}
+
+/// Turns records into fasta entries, keeping ids unique and NCBI indices
+/// running within each output file and id prefix.
+pub struct Converter {
+    preparer: Preparer,
+    /// Keyed by output name and rendered prefix.
+    scopes: HashMap<(String, String), Scope>,
+    /// Name of the output the next records are written to.
+    output: String,
+    prefix: Option<Template>,
+    /// `prefix` rendered for the record being converted.
+    record_prefix: String,
+}
+
+impl Converter {
+    pub fn new(output: Output, options: Options) -> Self {
+        Converter {
+            preparer: Preparer { output, options },
+            scopes: HashMap::new(),
+            output: String::new(),
+            prefix: None,
+            record_prefix: String::new(),
+        }
+    }
+
+    /// Put `prefix`, rendered for each record, before every id and header,
+    /// e.g. the assembly accession of the current input.
+    pub fn set_prefix(&mut self, prefix: Option<Template>) {
+        self.prefix = prefix;
+    }
+
+    /// Name the output the next records are written to. Ids and NCBI
+    /// indices run on from the records last written to the same output
+    /// under the same prefix, and start afresh otherwise.
+    pub fn set_output(&mut self, output: &str) {
+        output.clone_into(&mut self.output);
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.preparer.options
+    }
+
+    pub fn preparer(&self) -> &Preparer {
+        &self.preparer
+    }
+
+    pub fn convert(&mut self, record: &Record) -> io::Result<Vec<Entry>> {
+        let prepared = self.preparer.prepare(record)?;
+        self.finish(record, prepared)
+    }
+
+    /// Name the sequences `prepare` cut from `record`. Records must be
+    /// finished in input order for ids and indices to match a serial run.
+    pub fn finish(&mut self, record: &Record, prepared: Prepared) -> io::Result<Vec<Entry>> {
+        self.record_prefix = match &self.prefix {
+            Some(prefix) => prefix.render(record, None),
+            None => String::new(),
+        };
+        let mut entries = match self.preparer.output {
+            Output::Fna => vec![self.preparer.fna(record)],
+            Output::Faa | Output::Ffn => self.name(record, prepared.items)?,
+        };
+        if !self.record_prefix.is_empty() {
+            for entry in entries.iter_mut() {
+                entry.id.insert_str(0, &self.record_prefix);
+                entry.header.insert_str(0, &self.record_prefix);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Compare each CDS kept by the policies with its `/translation`.
+    pub fn verify(&self, record: &Record) -> io::Result<Vec<Verification>> {
+        let mut verifications = Vec::new();
+        for (index, feature) in record.features().iter().enumerate() {
+            if feature.kind != "CDS" || self.options().policies.flags(feature).is_none() {
+                continue;
+            }
+            let id = self.options().id_chain.base_id(record, index);
+            if let Some(verification) = verify_cds(record, feature, id)? {
+                verifications.push(verification);
+            }
+        }
+        Ok(verifications)
+    }
+
+    fn name(&mut self, record: &Record, items: Vec<Item>) -> io::Result<Vec<Entry>> {
+        let options = &self.preparer.options;
+        let file = match self.preparer.output {
+            Output::Faa => NcbiFile::Translated,
+            _ => NcbiFile::Genomic,
+        };
+        let scope = self
+            .scopes
+            .entry((self.output.clone(), self.record_prefix.clone()))
+            .or_default();
+        let mut named: Vec<(String, &Feature, (usize, Item))> = Vec::new();
+        for item in items {
+            let feature = &record.features()[item.feature];
+            let id = unique_id(&mut scope.ids, &self.record_prefix, item.base_id.clone());
+            scope.index += 1;
+            named.push((id, feature, (scope.index, item)));
+        }
+        options.sort.sort(&mut named);
+        let mut entries = Vec::new();
+        if let Some(intergenic) = &options.intergenic {
+            let sequence = record.sequence();
+            let neighbours: Vec<_> = named.iter().map(|(_, feature, _)| *feature).collect();
+            for region in intergenic_regions(
+                &neighbours,
+                sequence.len(),
+                record.is_circular(),
+                intergenic,
+            ) {
+                let index = |position: usize| named[position].2 .1.feature;
+                let id = format!(
+                    "{}_{}",
+                    options.id_chain.base_id(record, index(region.left)),
+                    options.id_chain.base_id(record, index(region.right))
+                );
+                let mut header = id.clone();
+                for (key, position) in [("left_gene", region.left), ("right_gene", region.right)] {
+                    if let Some(gene) = neighbours[position].qualifier("gene") {
+                        header.push_str(&format!(" [{}={}]", key, gene));
+                    }
+                }
+                header.push_str(&format!(
+                    " [location={}] [orientation={}]",
+                    region.location, region.orientation
+                ));
+                entries.push(Entry {
+                    id,
+                    header,
+                    sequence: region.location.extract(sequence)?,
+                    source: None,
+                });
+            }
+            return Ok(entries);
+        }
+        for (id, feature, (index, item)) in named {
+            let flags = if options.ncbi {
+                ""
+            } else {
+                item.flags.as_str()
+            };
+            for piece in item.pieces {
+                // a flank written on its own is named after its side
+                let id = match piece.side {
+                    Some(side) => format!("{}_{}", id, side),
+                    None => id.clone(),
+                };
+                let key = if options.ncbi {
+                    ncbi_header(record, feature, file, index)
+                } else {
+                    match &options.header {
+                        Some(template) => template.render_with_id(record, feature, &id),
+                        None => id.clone(),
+                    }
+                };
+                let header = match &piece.location {
+                    Some(location) => format!("{}{} [location={}]", key, flags, location),
+                    None => format!("{}{}", key, flags),
+                };
+                entries.push(Entry {
+                    id,
+                    header,
+                    sequence: piece.sequence,
+                    source: item.source,
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// `id`, made unique among the ids already handed out under the same prefix.
+fn unique_id(ids: &mut UniqueIds, prefix: &str, id: String) -> String {
+    let unique = ids.unique(format!("{}{}", prefix, id));
+    unique[prefix.len()..].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gbk::{self, GbkRead};
+
+    /// A 60 bp record with the feature table lines given.
+    fn record(features: &str) -> Record {
+        let text = format!(
+            "LOCUS       T1  60 bp    DNA     linear   BCT 01-JAN-2020\n\
+             ACCESSION   T1\n\
+             VERSION     T1.1\n\
+             FEATURES             Location/Qualifiers\n\
+             {}\
+             ORIGIN\n\
+             \x20       1 atgaaacccg ggttttaaaa ccccggggtt ttaaaacccc ggggttttaa\n\
+             \x20      51 aaccccgggg\n\
+             //\n",
+            features
+        );
+        let mut record = Record::new();
+        gbk::Reader::from_bufread(text.as_bytes())
+            .read(&mut record)
+            .unwrap();
+        record
+    }
+
+    fn convert(output: Output, options: Options, record: &Record) -> Vec<(String, String)> {
+        Converter::new(output, options)
+            .convert(record)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.header, String::from_utf8(entry.sequence).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn extracts_the_keys_asked_for() {
+        let record = record(
+            "     gene            1..18\n\
+             \x20                    /locus_tag=\"T_1\"\n\
+             \x20    CDS             1..18\n\
+             \x20                    /locus_tag=\"T_1\"\n\
+             \x20    tRNA            complement(join(21..25,31..35))\n\
+             \x20                    /locus_tag=\"T_2\"\n",
+        );
+        let trna = Options {
+            kinds: vec!["tRNA".to_string()],
+            ..Options::default()
+        };
+        assert_eq!(
+            convert(Output::Ffn, trna, &record),
+            vec![("T_2".to_string(), "tttaacgggg".to_string())]
+        );
+        let both = Options {
+            kinds: vec!["CDS".to_string(), "gene".to_string()],
+            ..Options::default()
+        };
+        let headers: Vec<String> = convert(Output::Ffn, both, &record)
+            .into_iter()
+            .map(|(header, _)| header)
+            .collect();
+        assert_eq!(headers, vec!["T_1_gene", "T_1_CDS"]);
+        let every = Options {
+            kinds: Vec::new(),
+            ..Options::default()
+        };
+        assert_eq!(convert(Output::Ffn, every, &record).len(), 3);
+    }
+}
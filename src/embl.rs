@@ -0,0 +1,368 @@
+use crate::compression;
+use crate::gbk::GbkRead;
+use crate::lines::{self, LineReader};
+use crate::record::{self, FeatureTable, Record};
+use anyhow::Context;
+use std::convert::AsRef;
+use std::io;
+use std::path::Path;
+
+/// An Embl reader, yielding the same records as the Gbk reader.
+#[derive(Debug)]
+pub struct Reader<B> {
+    lines: LineReader<B>,
+}
+
+impl Reader<Box<dyn io::BufRead + Send>> {
+    /// Read Embl from given file path. Gzip, BGZF, bzip2, xz and zstd
+    /// files are decompressed transparently.
+    pub fn from_file<P: AsRef<Path> + std::fmt::Debug>(path: P) -> anyhow::Result<Self> {
+        compression::open(&path)
+            .map(Reader::from_bufread)
+            .with_context(|| format!("Failed to read Embl from {:#?}", path))
+    }
+}
+
+impl<R> Reader<io::BufReader<R>>
+where
+    R: io::Read,
+{
+    /// Create a new Embl reader given an instance of `io::Read`
+    pub fn new(reader: R) -> Self {
+        Reader::from_bufread(io::BufReader::new(reader))
+    }
+}
+
+impl<B> Reader<B>
+where
+    B: io::BufRead,
+{
+    pub fn from_bufread(bufreader: B) -> Self {
+        Reader {
+            lines: LineReader::new(bufreader),
+        }
+    }
+    pub fn records(self) -> Records<B> {
+        Records {
+            reader: self,
+            error_has_occurred: false,
+        }
+    }
+}
+
+fn invalid_header(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Malformed ID line: {}", line.trim_end()),
+    )
+}
+
+impl<B> GbkRead for Reader<B>
+where
+    B: io::BufRead,
+{
+    fn read(&mut self, record: &mut Record) -> io::Result<()> {
+        record.rec_clear();
+        let mut features = FeatureTable::default();
+        while let Some(line) = self.lines.next_line()? {
+            if line.starts_with(b"SQ") {
+                while let Some(line) = self.lines.next_line()? {
+                    if line.starts_with(b"//") {
+                        break;
+                    }
+                    lines::push_bases(&mut record.sequence, lines::before_last_word(line));
+                }
+                break;
+            } else if line.starts_with(b"//") {
+                break;
+            }
+            let line = lines::text(line)?;
+            if line.starts_with("ID") {
+                record.rec_clear();
+                let mut fields = line.split(';');
+                let (id, version, topology) = (fields.next(), fields.next(), fields.next());
+                record.circular = topology.map(str::trim) == Some("circular");
+                record.molecule = fields.next().unwrap_or_default().trim().to_string();
+                record.id = id
+                    .and_then(|id| id.split_whitespace().nth(1))
+                    .ok_or_else(|| invalid_header(line))?
+                    .trim()
+                    .to_string();
+                record.length = fields
+                    .nth(2)
+                    .and_then(|lens| lens.split_whitespace().next())
+                    .and_then(|lens| lens.trim().parse::<u32>().ok())
+                    .ok_or_else(|| invalid_header(line))?;
+                if let Some(sv) = version.map(str::trim).and_then(|sv| sv.strip_prefix("SV ")) {
+                    record.version = format!("{}.{}", record.id, sv.trim());
+                }
+            } else if line.starts_with("AC   ") && record.accession.is_empty() {
+                record.accession = line[5..]
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+            } else if let Some(date) = line.strip_prefix("DT   ") {
+                // the last DT line is the last update
+                record.date = date
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+            } else if let Some(description) = line.strip_prefix("DE   ") {
+                if !record.definition.is_empty() {
+                    record.definition.push(' ');
+                }
+                record.definition.push_str(description.trim());
+            } else if line.starts_with("OS   ") && record.organism.is_empty() {
+                record.organism = line[5..].trim().to_string();
+            } else if let Some(lineage) = line.strip_prefix("OC   ") {
+                if !record.taxonomy.is_empty() {
+                    record.taxonomy.push(' ');
+                }
+                record
+                    .taxonomy
+                    .push_str(lineage.trim().trim_end_matches('.'));
+            } else if let Some(entry) = line.strip_prefix("FT   ") {
+                features.push_line(entry)?;
+            }
+        }
+        record.features = features.finish()?;
+        Ok(())
+    }
+}
+
+/// An iterator over the records of an Embl file.
+pub struct Records<B>
+where
+    B: io::BufRead,
+{
+    reader: Reader<B>,
+    error_has_occurred: bool,
+}
+
+impl<B> Iterator for Records<B>
+where
+    B: io::BufRead,
+{
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        if self.error_has_occurred {
+            None
+        } else {
+            let mut record = Record::new();
+            match self.reader.read(&mut record) {
+                Ok(()) if record.is_empty() => None,
+                Ok(()) => Some(Ok(record)),
+                Err(err) => {
+                    self.error_has_occurred = true;
+                    Some(Err(err))
+                }
+            }
+        }
+    }
+}
+
+/// Words of the DE lines per line.
+const DESCRIPTION_WIDTH: usize = 75;
+
+/// Write `record` as an EMBL flat file record, ending with its `//` line.
+pub fn write<W: io::Write>(out: &mut W, record: &Record) -> io::Result<()> {
+    let topology = if record.circular {
+        "circular"
+    } else {
+        "linear"
+    };
+    let version = match record.version.rsplit_once('.') {
+        Some((_, sv)) => format!("SV {}", sv),
+        None => "SV".to_string(),
+    };
+    // `DNA` in GenBank is `genomic DNA` here
+    let molecule = match record.molecule.as_str() {
+        "" => "genomic DNA".to_string(),
+        "DNA" | "RNA" => format!("genomic {}", record.molecule),
+        molecule => molecule.to_string(),
+    };
+    writeln!(
+        out,
+        "ID   {}; {}; {}; {}; STD; UNC; {} BP.",
+        record.id,
+        version,
+        topology,
+        molecule,
+        record.sequence.len()
+    )?;
+    writeln!(out, "XX")?;
+    writeln!(out, "AC   {};", record.accession())?;
+    writeln!(out, "XX")?;
+    for line in lines::wrap(&record.definition, DESCRIPTION_WIDTH, Some(b' ')) {
+        writeln!(out, "DE   {}", line)?;
+    }
+    writeln!(out, "XX")?;
+    if !record.organism.is_empty() {
+        writeln!(out, "OS   {}", record.organism)?;
+        if !record.taxonomy.is_empty() {
+            let lineage = format!("{}.", record.taxonomy);
+            for line in lines::wrap(&lineage, DESCRIPTION_WIDTH, Some(b' ')) {
+                writeln!(out, "OC   {}", line)?;
+            }
+        }
+        writeln!(out, "XX")?;
+    }
+    writeln!(out, "FH   Key             Location/Qualifiers")?;
+    writeln!(out, "FH")?;
+    record::write_features(out, "FT   ", &record.features)?;
+    writeln!(out, "XX")?;
+    let count = |bases: &[u8]| {
+        record
+            .sequence
+            .iter()
+            .filter(|b| bases.contains(&b.to_ascii_uppercase()))
+            .count()
+    };
+    let (a, c, g, t) = (count(b"A"), count(b"C"), count(b"G"), count(b"T"));
+    writeln!(
+        out,
+        "SQ   Sequence {} BP; {} A; {} C; {} G; {} T; {} other;",
+        record.sequence.len(),
+        a,
+        c,
+        g,
+        t,
+        record.sequence.len() - a - c - g - t
+    )?;
+    let mut written = 0;
+    for line in record.sequence.chunks(60) {
+        let blocks: Vec<&str> = line
+            .chunks(10)
+            .map(|block| std::str::from_utf8(block).unwrap_or_default())
+            .collect();
+        written += line.len();
+        writeln!(out, "     {:<65}{:>10}", blocks.join(" "), written)?;
+    }
+    writeln!(out, "//")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "\
+ID   X56734; SV 2; circular; DNA; STD; PRO; 40 BP.
+XX
+AC   X56734; S46826;
+XX
+DE   Escherichia coli test
+DE   sequence.
+XX
+OS   Escherichia coli
+OC   Bacteria; Pseudomonadota;
+OC   Escherichia.
+XX
+FH   Key             Location/Qualifiers
+FT   CDS             complement(join(1..10,
+FT                   21..40))
+FT                   /locus_tag=\"T_1\"
+FT                   /translation=\"MKKK
+FT                   LLL\"
+XX
+SQ   Sequence 40 BP; 10 A; 10 C; 10 G; 10 T; 0 other;
+     atgaaacccg ggttttaaaa ccccggggtt ttaaaacccc        40
+//
+";
+
+    #[test]
+    fn reads_header_features_and_sequence() {
+        let mut record = Record::new();
+        Reader::from_bufread(RECORD.as_bytes())
+            .read(&mut record)
+            .unwrap();
+        assert_eq!(record.id(), "X56734");
+        assert_eq!(record.length(), 40);
+        assert!(record.is_circular());
+        assert_eq!(record.molecule(), "DNA");
+        assert_eq!(record.accession(), "X56734");
+        assert_eq!(record.version(), "X56734.2");
+        assert_eq!(record.definition(), "Escherichia coli test sequence.");
+        assert_eq!(record.organism(), "Escherichia coli");
+        assert_eq!(
+            record.taxonomy().collect::<Vec<_>>(),
+            vec!["Bacteria", "Pseudomonadota", "Escherichia"]
+        );
+        assert_eq!(
+            record.sequence(),
+            b"atgaaacccgggttttaaaaccccggggttttaaaacccc"
+        );
+        let cds = &record.features()[0];
+        assert_eq!(cds.location.to_string(), "complement(join(1..10,21..40))");
+        assert_eq!(cds.qualifier("translation"), Some("MKKKLLL"));
+    }
+
+    #[test]
+    fn reads_the_same_whatever_the_buffer_size() {
+        let text = RECORD.repeat(2);
+        let expected: Vec<String> = Reader::from_bufread(text.as_bytes())
+            .records()
+            .map(|record| format!("{:?}", record.unwrap()))
+            .collect();
+        assert_eq!(expected.len(), 2);
+        for capacity in [1, 3, 16, 100] {
+            let reader = io::BufReader::with_capacity(capacity, text.as_bytes());
+            let records: Vec<String> = Reader::from_bufread(reader)
+                .records()
+                .map(|record| format!("{:?}", record.unwrap()))
+                .collect();
+            assert_eq!(records, expected, "capacity {}", capacity);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let error = Reader::from_bufread(&b"ID   X56734; SV 2; linear\n//\n"[..])
+            .records()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Malformed ID line"));
+    }
+
+    #[test]
+    fn writes_slices_without_the_parent_version() {
+        let dated = RECORD.replace(
+            "XX\nAC",
+            "DT   12-SEP-1991 (Rel. 29, Created)\n\
+             DT   15-MAR-2004 (Rel. 79, Last updated, Version 2)\n\
+             XX\nAC",
+        );
+        let mut record = Record::new();
+        Reader::from_bufread(dated.as_bytes())
+            .read(&mut record)
+            .unwrap();
+        assert_eq!(record.date(), "15-MAR-2004");
+        let mut written = Vec::new();
+        write(&mut written, &record).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(
+            written.lines().next().unwrap(),
+            "ID   X56734; SV 2; circular; genomic DNA; STD; UNC; 40 BP."
+        );
+
+        let mut written = Vec::new();
+        write(&mut written, &record.slice(0, 20).unwrap()).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(
+            written.lines().next().unwrap(),
+            "ID   X56734_1-20; SV; linear; genomic DNA; STD; UNC; 20 BP."
+        );
+        let mut again = Record::new();
+        Reader::from_bufread(written.as_bytes())
+            .read(&mut again)
+            .unwrap();
+        assert_eq!(again.id(), "X56734_1-20");
+        assert_eq!(again.accession(), "X56734");
+        assert_eq!(again.version(), "X56734");
+        assert_eq!(again.sequence(), &record.sequence()[..20]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+/// Line width used for nucleotide output unless `--width` is given.
+pub const NUCLEOTIDE_WIDTH: usize = 80;
+/// Line width used for protein output unless `--width` is given.
+pub const PROTEIN_WIDTH: usize = 60;
+
+/// Case applied to sequence letters as they are written.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Case {
+    #[default]
+    Preserve,
+    Upper,
+    Lower,
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Case::Preserve => "preserve",
+            Case::Upper => "upper",
+            Case::Lower => "lower",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Case {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "preserve" | "keep" => Ok(Case::Preserve),
+            "upper" => Ok(Case::Upper),
+            "lower" => Ok(Case::Lower),
+            _ => Err(format!("unknown case {:?}", s)),
+        }
+    }
+}
+
+/// Buffered FASTA writer wrapping sequences at `width` letters per line,
+/// or not at all when `width` is 0.
+pub struct Writer<W: Write> {
+    inner: BufWriter<W>,
+    width: usize,
+    case: Case,
+}
+
+impl Writer<io::Stdout> {
+    pub fn stdout(width: usize, case: Case) -> Self {
+        Writer::new(io::stdout(), width, case)
+    }
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W, width: usize, case: Case) -> Self {
+        Writer {
+            inner: BufWriter::new(inner),
+            width,
+            case,
+        }
+    }
+
+    /// Write `>header` followed by the wrapped sequence.
+    pub fn write_record(&mut self, header: &str, sequence: &[u8]) -> io::Result<()> {
+        self.inner.write_all(b">")?;
+        self.inner.write_all(header.as_bytes())?;
+        self.inner.write_all(b"\n")?;
+        let width = if self.width == 0 {
+            sequence.len().max(1)
+        } else {
+            self.width
+        };
+        let mut line = Vec::with_capacity(width + 1);
+        for chunk in sequence.chunks(width) {
+            line.clear();
+            match self.case {
+                Case::Preserve => line.extend_from_slice(chunk),
+                Case::Upper => line.extend(chunk.iter().map(u8::to_ascii_uppercase)),
+                Case::Lower => line.extend(chunk.iter().map(u8::to_ascii_lowercase)),
+            }
+            line.push(b'\n');
+            self.inner.write_all(&line)?;
+        }
+        if sequence.is_empty() {
+            self.inner.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` unchanged, e.g. a whole flat file record.
+    pub fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_all(bytes)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Flush and hand back the wrapped writer.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.inner.into_inner().map_err(|err| err.into_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(width: usize, case: Case, sequence: &[u8]) -> String {
+        let mut writer = Writer::new(Vec::new(), width, case);
+        writer.write_record("T_1 thing", sequence).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn wraps_at_the_width_given() {
+        assert_eq!(
+            written(4, Case::Preserve, b"ATGaaaTAG"),
+            ">T_1 thing\nATGa\naaTA\nG\n"
+        );
+        assert_eq!(
+            written(3, Case::Preserve, b"ATGaaa"),
+            ">T_1 thing\nATG\naaa\n"
+        );
+        assert_eq!(
+            written(0, Case::Preserve, b"ATGaaaTAG"),
+            ">T_1 thing\nATGaaaTAG\n"
+        );
+        assert_eq!(written(80, Case::Preserve, b""), ">T_1 thing\n\n");
+    }
+
+    #[test]
+    fn changes_case_of_the_sequence_only() {
+        assert_eq!(
+            written(0, Case::Upper, b"ATGaaa-*"),
+            ">T_1 thing\nATGAAA-*\n"
+        );
+        assert_eq!(written(0, Case::Lower, b"ATGaaa"), ">T_1 thing\natgaaa\n");
+        assert_eq!("keep".parse::<Case>(), Ok(Case::Preserve));
+        assert_eq!("UPPER".parse::<Case>(), Ok(Case::Upper));
+        assert!("title".parse::<Case>().is_err());
+    }
+}
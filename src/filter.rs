@@ -0,0 +1,562 @@
+use crate::policy::{is_pseudo, partial_ends};
+use crate::record::Feature;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A feature filter such as
+/// `type == CDS && product ~ /transposase/i && length >= 300 && !pseudo`.
+///
+/// Terms compare a field with a value, or test a field on its own. The
+/// fields are `type`, `strand` (`+` or `-`), `length`, `start` and `end`
+/// (1-based), `location`, `partial` and `pseudo` (either `/pseudo` or
+/// `/pseudogene`); any other name is a qualifier, true on its own when the
+/// feature has it. The operators are `==`, `!=`, `<`, `<=`, `>`, `>=`,
+/// comparing numbers as numbers, and `~` and `!~` against a `/regex/`,
+/// optionally followed by `i` to ignore case, or text the value contains.
+/// Terms combine with `!`, `&&`, `||` and parentheses. A repeated
+/// qualifier matches when any of its values does.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    text: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn matches(&self, feature: &Feature) -> bool {
+        self.expr.matches(feature)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |message: String| format!("invalid filter {:?}: {}", s, message);
+        let tokens = tokenize(s).map_err(error)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or().map_err(error)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(error(format!("unexpected {}", token)));
+        }
+        Ok(Filter {
+            text: s.to_string(),
+            expr,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Present(Field),
+    Compare(Field, Op, Value),
+}
+
+impl Expr {
+    fn matches(&self, feature: &Feature) -> bool {
+        match self {
+            Expr::Or(a, b) => a.matches(feature) || b.matches(feature),
+            Expr::And(a, b) => a.matches(feature) && b.matches(feature),
+            Expr::Not(a) => !a.matches(feature),
+            Expr::Present(field) => field.present(feature),
+            Expr::Compare(field, op, value) => {
+                let values = field.values(feature);
+                match op {
+                    Op::Ne => !values.iter().any(|v| value.compare(v) == Ordering::Equal),
+                    Op::NotMatch => !values.iter().any(|v| value.is_match(v)),
+                    Op::Match => values.iter().any(|v| value.is_match(v)),
+                    _ => values.iter().any(|v| op.accepts(value.compare(v))),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Field {
+    Type,
+    Strand,
+    Length,
+    Start,
+    End,
+    Location,
+    Partial,
+    Pseudo,
+    Qualifier(String),
+}
+
+impl Field {
+    fn new(name: &str) -> Field {
+        match name {
+            "type" | "key" => Field::Type,
+            "strand" => Field::Strand,
+            "length" => Field::Length,
+            "start" => Field::Start,
+            "end" => Field::End,
+            "location" => Field::Location,
+            "partial" => Field::Partial,
+            "pseudo" => Field::Pseudo,
+            _ => Field::Qualifier(name.to_string()),
+        }
+    }
+
+    fn present(&self, feature: &Feature) -> bool {
+        match self {
+            Field::Partial => partial_ends(feature).is_some(),
+            Field::Pseudo => is_pseudo(feature),
+            Field::Qualifier(key) => feature.has_qualifier(key),
+            Field::Length => !feature.location.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// The values compared, with `partial` as `5'`, `3'` or `5',3'` and
+    /// `pseudo` as the `/pseudogene` type.
+    fn values(&self, feature: &Feature) -> Vec<String> {
+        let location = &feature.location;
+        match self {
+            Field::Type => vec![feature.kind.clone()],
+            Field::Strand => vec![if location.strand() == -1 { "-" } else { "+" }.to_string()],
+            Field::Length => vec![location.len().to_string()],
+            Field::Start => vec![(location.start() + 1).to_string()],
+            Field::End => vec![location.end().to_string()],
+            Field::Location => vec![location.to_string()],
+            Field::Partial => partial_ends(feature)
+                .map(str::to_string)
+                .into_iter()
+                .collect(),
+            Field::Pseudo => qualifier_values(feature, "pseudogene"),
+            Field::Qualifier(key) => qualifier_values(feature, key),
+        }
+    }
+}
+
+fn qualifier_values(feature: &Feature, key: &str) -> Vec<String> {
+    feature
+        .qualifiers
+        .iter()
+        .filter(|(k, _)| k == key)
+        .map(|(_, v)| v.clone())
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+impl Op {
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Match | Op::NotMatch => false,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Text(String),
+    Pattern(Regex),
+}
+
+impl Value {
+    /// How the field value `value` orders against this one, numerically
+    /// when both are numbers.
+    fn compare(&self, value: &str) -> Ordering {
+        let text: &str = match self {
+            Value::Text(text) => text,
+            Value::Pattern(pattern) => pattern.as_str(),
+        };
+        match (value.trim().parse::<f64>(), text.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            _ => value.cmp(text),
+        }
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        match self {
+            Value::Pattern(pattern) => pattern.is_match(value),
+            Value::Text(text) => value.contains(text.as_str()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Pattern(String, bool),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{:?}", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+            Token::Pattern(pattern, _) => write!(f, "/{}/", pattern),
+            Token::Op(op) => write!(f, "{:?}", op.to_string()),
+            Token::And => write!(f, "\"&&\""),
+            Token::Or => write!(f, "\"||\""),
+            Token::Not => write!(f, "\"!\""),
+            Token::Open => write!(f, "\"(\""),
+            Token::Close => write!(f, "\")\""),
+        }
+    }
+}
+
+/// Characters that end a bare word.
+const SPECIAL: &str = "()&|!=<>~\"'";
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+        let token = match (c, next) {
+            _ if c.is_whitespace() => continue,
+            ('(', _) => Token::Open,
+            (')', _) => Token::Close,
+            ('&', Some('&')) => Token::And,
+            ('|', Some('|')) => Token::Or,
+            ('=', Some('=')) => Token::Op(Op::Eq),
+            ('!', Some('=')) => Token::Op(Op::Ne),
+            ('!', Some('~')) => Token::Op(Op::NotMatch),
+            ('<', Some('=')) => Token::Op(Op::Le),
+            ('>', Some('=')) => Token::Op(Op::Ge),
+            ('!', _) => Token::Not,
+            ('~', _) => Token::Op(Op::Match),
+            ('<', _) => Token::Op(Op::Lt),
+            ('>', _) => Token::Op(Op::Gt),
+            ('"', _) | ('\'', _) => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => quoted.extend(chars.next().map(|(_, e)| e)),
+                        Some((_, other)) => quoted.push(other),
+                        None => return Err(format!("unterminated string at column {}", i + 1)),
+                    }
+                }
+                tokens.push(Token::Quoted(quoted));
+                continue;
+            }
+            ('/', _) if matches!(tokens.last(), Some(Token::Op(_))) => {
+                let mut pattern = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '/')) => break,
+                        Some((_, '\\')) if chars.peek().map(|&(_, e)| e) == Some('/') => {
+                            pattern.push('/');
+                            chars.next();
+                        }
+                        Some((_, other)) => pattern.push(other),
+                        None => return Err(format!("unterminated /regex/ at column {}", i + 1)),
+                    }
+                }
+                let ignore_case = chars.peek().map(|&(_, f)| f) == Some('i');
+                if ignore_case {
+                    chars.next();
+                }
+                tokens.push(Token::Pattern(pattern, ignore_case));
+                continue;
+            }
+            ('&', _) | ('|', _) | ('=', _) => {
+                return Err(format!("expected {}{} at column {}", c, c, i + 1))
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&(_, w)) = chars.peek() {
+                    if w.is_whitespace() || SPECIAL.contains(w) {
+                        break;
+                    }
+                    word.push(w);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+                continue;
+            }
+        };
+        if matches!(
+            token,
+            Token::And | Token::Or | Token::Op(Op::Eq | Op::Ne | Op::NotMatch | Op::Le | Op::Ge)
+        ) {
+            chars.next();
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    Some(token) => Err(format!("expected \")\", found {}", token)),
+                    None => Err("expected \")\"".to_string()),
+                }
+            }
+            Some(Token::Word(name)) => self.term(Field::new(&name)),
+            Some(token) => Err(format!("expected a field name, found {}", token)),
+            None => Err("expected a field name".to_string()),
+        }
+    }
+
+    fn term(&mut self, field: Field) -> Result<Expr, String> {
+        let op = match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => *op,
+            _ => return Ok(Expr::Present(field)),
+        };
+        self.pos += 1;
+        let value = match (self.next(), op) {
+            (Some(Token::Pattern(pattern, ignore_case)), Op::Match | Op::NotMatch) => {
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .map_err(|err| format!("bad regex /{}/: {}", pattern, err))?;
+                Value::Pattern(regex)
+            }
+            (Some(Token::Pattern(pattern, _)), _) => {
+                return Err(format!("/{}/ can only follow ~ or !~", pattern))
+            }
+            (Some(Token::Word(text)), _) | (Some(Token::Quoted(text)), _) => Value::Text(text),
+            (Some(token), _) => {
+                return Err(format!("expected a value after {}, found {}", op, token))
+            }
+            (None, _) => return Err(format!("expected a value after {}", op)),
+        };
+        let value = match (&field, value) {
+            (Field::Strand, Value::Text(text)) => Value::Text(strand(&text)?.to_string()),
+            (_, value) => value,
+        };
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+/// `+` or `-` for the ways of writing a strand.
+fn strand(text: &str) -> Result<&'static str, String> {
+    match text {
+        "+" | "1" | "+1" | "plus" | "forward" => Ok("+"),
+        "-" | "-1" | "minus" | "reverse" => Ok("-"),
+        _ => Err(format!("unknown strand {:?}, expected + or -", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+
+    fn feature(kind: &str, location: &str, qualifiers: &[(&str, &str)]) -> Feature {
+        Feature {
+            kind: kind.to_string(),
+            location: Location::parse(location).unwrap(),
+            qualifiers: qualifiers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    fn cds() -> Feature {
+        feature(
+            "CDS",
+            "complement(<101..400)",
+            &[
+                ("locus_tag", "TAG_1"),
+                ("product", "IS3 family Transposase"),
+                ("db_xref", "GI:5"),
+                ("db_xref", "UniProt:Q5"),
+            ],
+        )
+    }
+
+    fn matches(filter: &str, feature: &Feature) -> bool {
+        filter.parse::<Filter>().unwrap().matches(feature)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let cds = cds();
+        assert!(matches(
+            "type == gene && length > 1000 || type == CDS",
+            &cds
+        ));
+        assert!(matches(
+            "type == CDS || type == gene && length > 1000",
+            &cds
+        ));
+        assert!(!matches(
+            "(type == CDS || type == gene) && length > 1000",
+            &cds
+        ));
+        assert!(!matches("!type == CDS || length > 1000", &cds));
+        assert!(matches("!(type == gene || length > 1000)", &cds));
+    }
+
+    #[test]
+    fn tells_not_from_not_equal_and_not_match() {
+        let cds = cds();
+        assert!(matches("!pseudo", &cds));
+        assert!(matches("type != gene", &cds));
+        assert!(!matches("!type != gene", &cds));
+        assert!(matches("product !~ /integrase/", &cds));
+        assert!(!matches("!product !~ /integrase/", &cds));
+        assert!(matches("!(product !~ /transposase/i)", &cds));
+    }
+
+    #[test]
+    fn compares_fields_and_numbers() {
+        let cds = cds();
+        assert!(matches("strand == - && strand == minus", &cds));
+        assert!(matches("start == 101 && end == 400 && length == 300", &cds));
+        assert!(matches("length >= 300 && length < 1000.5", &cds));
+        // the < of a minus strand feature is its 3' end
+        assert!(matches("partial == \"3'\" && partial", &cds));
+        assert!(matches("location == 'complement(<101..400)'", &cds));
+        assert!(matches("db_xref == UniProt:Q5 && db_xref ~ GI", &cds));
+    }
+
+    #[test]
+    fn regex_flags_and_quoted_values() {
+        let cds = cds();
+        assert!(!matches("product ~ /transposase/", &cds));
+        assert!(matches("product ~ /transposase/i", &cds));
+        assert!(matches("product ~ /^IS\\d+ family/", &cds));
+        assert!(matches("product == \"IS3 family Transposase\"", &cds));
+        assert!(matches("product == 'IS3 family Transposase'", &cds));
+        assert!(matches("product ~ \"family\"", &cds));
+        assert!(matches("locus_tag ~ /a\\/b|TAG/", &cds));
+    }
+
+    #[test]
+    fn missing_qualifiers() {
+        let cds = cds();
+        assert!(!matches("gene", &cds));
+        assert!(!matches("gene == abcD", &cds));
+        assert!(matches("gene != abcD", &cds));
+        assert!(matches("gene !~ /abc/", &cds));
+        assert!(!matches("gene ~ /./", &cds));
+        let pseudo = feature("gene", "1..90", &[("pseudogene", "unprocessed")]);
+        assert!(matches("pseudo && pseudo == unprocessed", &pseudo));
+    }
+
+    #[test]
+    fn reports_what_is_wrong() {
+        let error = |text: &str| text.parse::<Filter>().unwrap_err();
+        assert_eq!(
+            error("type =="),
+            "invalid filter \"type ==\": expected a value after =="
+        );
+        assert_eq!(
+            error("type = CDS"),
+            "invalid filter \"type = CDS\": expected == at column 6"
+        );
+        assert_eq!(
+            error("(type == CDS"),
+            "invalid filter \"(type == CDS\": expected \")\""
+        );
+        assert_eq!(
+            error("type == CDS gene"),
+            "invalid filter \"type == CDS gene\": unexpected \"gene\""
+        );
+        assert_eq!(
+            error("product == /x/"),
+            "invalid filter \"product == /x/\": /x/ can only follow ~ or !~"
+        );
+        assert_eq!(
+            error("product ~ 'x"),
+            "invalid filter \"product ~ 'x\": unterminated string at column 11"
+        );
+        assert_eq!(
+            error("strand == up"),
+            "invalid filter \"strand == up\": unknown strand \"up\", expected + or -"
+        );
+        assert!(error("product ~ /(/").contains("bad regex /(/"));
+        assert!(error("&& type").contains("expected a field name, found \"&&\""));
+    }
+}
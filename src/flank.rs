@@ -0,0 +1,212 @@
+use crate::location::{Location, Segment};
+
+/// Bases to add before the 5' end and after the 3' end of a feature.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Flanks {
+    pub upstream: usize,
+    pub downstream: usize,
+}
+
+/// Plus strand pieces covering `[from, to)`, wrapping around the origin of
+/// circular sequences and clipped to the sequence on linear ones.
+fn window(from: isize, to: isize, length: usize, circular: bool) -> Vec<Segment> {
+    let length = length as isize;
+    let mut pieces = Vec::new();
+    if length == 0 || from >= to {
+        return pieces;
+    }
+    if !circular {
+        let (from, to) = (from.max(0), to.min(length));
+        if from < to {
+            pieces.push(Segment {
+                start: from as usize,
+                end: to as usize,
+                strand: 1,
+                ..Default::default()
+            });
+        }
+        return pieces;
+    }
+    let to = to.min(from + length);
+    let mut pos = from;
+    while pos < to {
+        let offset = pos.rem_euclid(length);
+        let step = (length - offset).min(to - pos);
+        pieces.push(Segment {
+            start: offset as usize,
+            end: (offset + step) as usize,
+            strand: 1,
+            ..Default::default()
+        });
+        pos += step;
+    }
+    pieces
+}
+
+/// Pieces of the window on the feature's strand, in 5' to 3' order.
+fn stranded(mut pieces: Vec<Segment>, strand: i32) -> Vec<Segment> {
+    if strand == -1 {
+        pieces.reverse();
+        for piece in pieces.iter_mut() {
+            piece.strand = -1;
+        }
+    }
+    pieces
+}
+
+/// Merge neighbouring segments that continue each other on the same strand.
+fn merge_adjacent(segments: Vec<Segment>) -> Vec<Segment> {
+    let mut merged: Vec<Segment> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        if let Some(last) = merged.last_mut() {
+            if last.strand == segment.strand && last.strand != -1 && last.end == segment.start {
+                last.end = segment.end;
+                last.partial_end = segment.partial_end;
+                continue;
+            }
+            if last.strand == segment.strand && last.strand == -1 && last.start == segment.end {
+                last.start = segment.start;
+                last.partial_start = segment.partial_start;
+                continue;
+            }
+        }
+        merged.push(segment);
+    }
+    merged
+}
+
+impl Flanks {
+    pub fn is_empty(&self) -> bool {
+        self.upstream == 0 && self.downstream == 0
+    }
+    /// The `upstream` bases before the feature's 5' end, strand aware.
+    pub fn upstream_of(&self, location: &Location, length: usize, circular: bool) -> Location {
+        let first = match location.segments.first() {
+            Some(first) => first,
+            None => return Location::default(),
+        };
+        let up = self.upstream as isize;
+        let pieces = if first.strand == -1 {
+            let end = first.end as isize;
+            window(end, end + up, length, circular)
+        } else {
+            let start = first.start as isize;
+            window(start - up, start, length, circular)
+        };
+        Location {
+            segments: merge_adjacent(stranded(pieces, first.strand)),
+            incomplete: false,
+        }
+    }
+    /// The `downstream` bases after the feature's 3' end, strand aware.
+    pub fn downstream_of(&self, location: &Location, length: usize, circular: bool) -> Location {
+        let last = match location.segments.last() {
+            Some(last) => last,
+            None => return Location::default(),
+        };
+        let down = self.downstream as isize;
+        let pieces = if last.strand == -1 {
+            let start = last.start as isize;
+            window(start - down, start, length, circular)
+        } else {
+            let end = last.end as isize;
+            window(end, end + down, length, circular)
+        };
+        Location {
+            segments: merge_adjacent(stranded(pieces, last.strand)),
+            incomplete: false,
+        }
+    }
+    /// The feature with both flanks attached.
+    pub fn extend(&self, location: &Location, length: usize, circular: bool) -> Location {
+        let mut segments = self.upstream_of(location, length, circular).segments;
+        segments.extend(location.segments.iter().cloned());
+        segments.extend(self.downstream_of(location, length, circular).segments);
+        Location {
+            segments: merge_adjacent(segments),
+            incomplete: location.incomplete,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLANKS: Flanks = Flanks {
+        upstream: 10,
+        downstream: 5,
+    };
+
+    fn flanks(text: &str, length: usize, circular: bool) -> (String, String, String) {
+        let location = Location::parse(text).unwrap();
+        (
+            FLANKS.upstream_of(&location, length, circular).to_string(),
+            FLANKS
+                .downstream_of(&location, length, circular)
+                .to_string(),
+            FLANKS.extend(&location, length, circular).to_string(),
+        )
+    }
+
+    #[test]
+    fn follows_the_strand() {
+        assert_eq!(
+            flanks("20..30", 100, false),
+            ("10..19".into(), "31..35".into(), "10..35".into())
+        );
+        assert_eq!(
+            flanks("complement(20..30)", 100, false),
+            (
+                "complement(31..40)".into(),
+                "complement(15..19)".into(),
+                "complement(15..40)".into()
+            )
+        );
+        assert_eq!(
+            flanks("join(20..30,40..50)", 100, false),
+            (
+                "10..19".into(),
+                "51..55".into(),
+                "join(10..30,40..55)".into()
+            )
+        );
+    }
+
+    #[test]
+    fn clips_linear_records() {
+        assert_eq!(
+            flanks("3..97", 100, false),
+            ("1..2".into(), "98..100".into(), "1..100".into())
+        );
+        let location = Location::parse("1..100").unwrap();
+        assert!(FLANKS
+            .upstream_of(&location, 100, false)
+            .segments
+            .is_empty());
+        assert!(FLANKS
+            .downstream_of(&location, 100, false)
+            .segments
+            .is_empty());
+    }
+
+    #[test]
+    fn wraps_circular_records() {
+        assert_eq!(
+            flanks("3..97", 100, true),
+            (
+                "join(93..100,1..2)".into(),
+                "join(98..100,1..2)".into(),
+                "join(93..100,1..100,1..2)".into()
+            )
+        );
+        assert_eq!(
+            flanks("complement(3..97)", 100, true),
+            (
+                "complement(join(98..100,1..7))".into(),
+                "complement(join(98..100,1..2))".into(),
+                "complement(join(98..100,1..100,1..7))".into()
+            )
+        );
+    }
+}
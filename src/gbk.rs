@@ -0,0 +1,433 @@
+use crate::compression;
+use crate::lines::{self, LineReader};
+use crate::record::{self, FeatureTable, Record};
+use anyhow::Context;
+use std::convert::AsRef;
+use std::io;
+use std::path::Path;
+
+/// A Gbk reader.
+pub trait GbkRead {
+    fn read(&mut self, record: &mut Record) -> io::Result<()>;
+}
+
+#[derive(Debug)]
+pub struct Reader<B> {
+    lines: LineReader<B>,
+}
+
+impl Reader<Box<dyn io::BufRead + Send>> {
+    /// Read Gbk from given file path in given format. Gzip, BGZF, bzip2,
+    /// xz and zstd files are decompressed transparently.
+    pub fn from_file<P: AsRef<Path> + std::fmt::Debug>(path: P) -> anyhow::Result<Self> {
+        compression::open(&path)
+            .map(Reader::from_bufread)
+            .with_context(|| format!("Failed to read Gbk from {:#?}", path))
+    }
+}
+
+impl<R> Reader<io::BufReader<R>>
+where
+    R: io::Read,
+{
+    /// Create a new Gbk reader given an instance of `io::Read` in given format
+    pub fn new(reader: R) -> Self {
+        Reader::from_bufread(io::BufReader::new(reader))
+    }
+}
+
+impl<B> Reader<B>
+where
+    B: io::BufRead,
+{
+    pub fn from_bufread(bufreader: B) -> Self {
+        Reader {
+            lines: LineReader::new(bufreader),
+        }
+    }
+    pub fn records(self) -> Records<B> {
+        Records {
+            reader: self,
+            error_has_occurred: false,
+        }
+    }
+}
+
+fn invalid_header(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Malformed LOCUS line: {}", line.trim_end()),
+    )
+}
+
+/// A LOCUS line date such as `09-MAR-2022`.
+fn is_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 11
+        && bytes[2] == b'-'
+        && bytes[6] == b'-'
+        && bytes[3..6].iter().all(u8::is_ascii_uppercase)
+        && [&bytes[..2], &bytes[7..]]
+            .concat()
+            .iter()
+            .all(u8::is_ascii_digit)
+}
+
+/// A three letter division code such as `BCT`.
+fn is_division(text: &str) -> bool {
+    text.len() == 3 && text.bytes().all(|b| b.is_ascii_uppercase())
+}
+
+impl<B> GbkRead for Reader<B>
+where
+    B: io::BufRead,
+{
+    fn read(&mut self, record: &mut Record) -> io::Result<()> {
+        record.rec_clear();
+        let mut features: Option<FeatureTable> = None;
+        let mut in_definition = false;
+        let mut in_organism = false;
+        while let Some(line) = self.lines.next_line()? {
+            if line.starts_with(b"ORIGIN") {
+                while let Some(line) = self.lines.next_line()? {
+                    if line.starts_with(b"//") {
+                        break;
+                    }
+                    lines::push_bases(&mut record.sequence, lines::after_first_word(line));
+                }
+                break;
+            } else if line.starts_with(b"//") {
+                break;
+            }
+            let line = lines::text(line)?;
+            if !line.starts_with("     ") {
+                let keyword = line.get(..12).unwrap_or_default().trim();
+                in_definition = keyword == "DEFINITION";
+                in_organism = keyword == "ORGANISM";
+            }
+            let value = line.get(12..).unwrap_or_default().trim();
+            if line.starts_with("LOCUS") {
+                record.rec_clear();
+                let mut header_iter = line.split_whitespace().skip(1);
+                record.id = header_iter
+                    .next()
+                    .ok_or_else(|| invalid_header(line))?
+                    .to_string();
+                record.length = header_iter
+                    .next()
+                    .and_then(|lens| lens.trim().parse::<u32>().ok())
+                    .ok_or_else(|| invalid_header(line))?;
+                let mut rest: Vec<&str> = header_iter.collect();
+                record.circular = rest.contains(&"circular");
+                if let Some(&date) = rest.last().filter(|date| is_date(date)) {
+                    record.date = date.to_string();
+                    rest.pop();
+                }
+                if let ["bp", molecule, ..] = rest[..] {
+                    if molecule != "linear" && molecule != "circular" {
+                        record.molecule = molecule.to_string();
+                    }
+                }
+                if let [.., "bp", _, _, division] | [.., "bp", _, division] = rest[..] {
+                    if is_division(division) {
+                        record.division = division.to_string();
+                    }
+                }
+            } else if features.is_none() && in_definition {
+                if !record.definition.is_empty() {
+                    record.definition.push(' ');
+                }
+                record.definition.push_str(value);
+            } else if line.starts_with("ACCESSION") {
+                record.accession = value
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+            } else if line.starts_with("VERSION") {
+                record.version = value
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+            } else if line.starts_with("  ORGANISM") {
+                record.organism = value.to_string();
+            } else if in_organism && features.is_none() {
+                if !record.taxonomy.is_empty() {
+                    record.taxonomy.push(' ');
+                }
+                record.taxonomy.push_str(value.trim_end_matches('.'));
+            } else if line.starts_with("FEATURES") {
+                features = Some(FeatureTable::default());
+            } else if let Some(entry) = line.strip_prefix("     ") {
+                if let Some(table) = features.as_mut() {
+                    table.push_line(entry)?;
+                }
+            } else if !line.starts_with(' ') {
+                if let Some(table) = features.take() {
+                    record.features = table.finish()?;
+                }
+            }
+        }
+        if let Some(table) = features.take() {
+            record.features = table.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the records of a Gbk file.
+pub struct Records<B>
+where
+    B: io::BufRead,
+{
+    reader: Reader<B>,
+    error_has_occurred: bool,
+}
+
+impl<B> Iterator for Records<B>
+where
+    B: io::BufRead,
+{
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        if self.error_has_occurred {
+            None
+        } else {
+            let mut record = Record::new();
+            match self.reader.read(&mut record) {
+                Ok(()) if record.is_empty() => None,
+                Ok(()) => Some(Ok(record)),
+                Err(err) => {
+                    self.error_has_occurred = true;
+                    Some(Err(err))
+                }
+            }
+        }
+    }
+}
+
+/// Words of the DEFINITION per line.
+const DEFINITION_WIDTH: usize = 68;
+
+/// Write `record` as a GenBank flat file record, ending with its `//` line.
+pub fn write<W: io::Write>(out: &mut W, record: &Record) -> io::Result<()> {
+    let topology = if record.circular {
+        "circular"
+    } else {
+        "linear"
+    };
+    // `genomic DNA` in EMBL is `DNA` here
+    let molecule = record.molecule.rsplit(' ').next().unwrap_or_default();
+    let molecule = if molecule.is_empty() { "DNA" } else { molecule };
+    // strandedness such as `ss-` goes in the three columns before the type
+    let (strands, molecule) = match molecule.split_once('-') {
+        Some((strands, rest)) if strands.len() == 2 => (&molecule[..3], rest),
+        _ => ("", molecule),
+    };
+    let division = if record.division.is_empty() {
+        "UNA"
+    } else {
+        &record.division
+    };
+    let date = if record.date.is_empty() {
+        "01-JAN-1980"
+    } else {
+        &record.date
+    };
+    writeln!(
+        out,
+        "LOCUS       {:<16} {:>11} bp {:>3}{:<6}  {:<8} {} {}",
+        record.id,
+        record.sequence.len(),
+        strands,
+        molecule,
+        topology,
+        division,
+        date
+    )?;
+    let definition = lines::wrap(&record.definition, DEFINITION_WIDTH, Some(b' '));
+    for (i, line) in definition.iter().enumerate() {
+        let keyword = if i == 0 { "DEFINITION" } else { "" };
+        writeln!(out, "{:<12}{}", keyword, line)?;
+    }
+    writeln!(out, "ACCESSION   {}", record.accession())?;
+    if !record.version.is_empty() {
+        writeln!(out, "VERSION     {}", record.version)?;
+    }
+    if !record.organism.is_empty() {
+        writeln!(out, "SOURCE      {}", record.organism)?;
+        writeln!(out, "  ORGANISM  {}", record.organism)?;
+        if !record.taxonomy.is_empty() {
+            let lineage = format!("{}.", record.taxonomy);
+            for line in lines::wrap(&lineage, DEFINITION_WIDTH, Some(b' ')) {
+                writeln!(out, "            {}", line)?;
+            }
+        }
+    }
+    writeln!(out, "FEATURES             Location/Qualifiers")?;
+    record::write_features(out, "     ", &record.features)?;
+    writeln!(out, "ORIGIN")?;
+    for (i, line) in record.sequence.chunks(60).enumerate() {
+        write!(out, "{:>9}", i * 60 + 1)?;
+        for block in line.chunks(10) {
+            out.write_all(b" ")?;
+            out.write_all(block)?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(out, "//")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "\
+LOCUS       NC_1                     120 bp    DNA     circular BCT 01-JAN-2020
+DEFINITION  Escherichia coli test
+            sequence.
+ACCESSION   NC_1 REGION: 1..120
+VERSION     NC_1.2
+SOURCE      Escherichia coli
+  ORGANISM  Escherichia coli
+            Bacteria; Pseudomonadota; Enterobacterales; Enterobacteriaceae;
+            Escherichia.
+FEATURES             Location/Qualifiers
+     source          1..120
+                     /organism=\"Escherichia coli\"
+     CDS             complement(join(1..10,
+                     21..40))
+                     /locus_tag=\"T_1\"
+                     /note=\"a \"\"quoted\"\" note that runs
+                     over two lines\"
+                     /translation=\"MKKK
+                     LLL\"
+                     /pseudo
+ORIGIN
+        1 atgaaacccg ggttttaaaa ccccggggtt ttaaaacccc ggggttttaa aaccccgggg
+       61 atgaaacccg ggttttaaaa ccccggggtt ttaaaacccc ggggttttaa aaccccgggg
+//
+";
+
+    #[test]
+    fn reads_header_features_and_sequence() {
+        let mut record = Record::new();
+        Reader::from_bufread(RECORD.as_bytes())
+            .read(&mut record)
+            .unwrap();
+        assert_eq!(record.id(), "NC_1");
+        assert_eq!(record.length(), 120);
+        assert!(record.is_circular());
+        assert_eq!(record.molecule(), "DNA");
+        assert_eq!(record.definition(), "Escherichia coli test sequence.");
+        assert_eq!(record.accession(), "NC_1");
+        assert_eq!(record.version(), "NC_1.2");
+        assert_eq!(record.organism(), "Escherichia coli");
+        assert_eq!(
+            record.taxonomy().collect::<Vec<_>>(),
+            vec![
+                "Bacteria",
+                "Pseudomonadota",
+                "Enterobacterales",
+                "Enterobacteriaceae",
+                "Escherichia"
+            ]
+        );
+        assert_eq!(record.sequence().len(), 120);
+        assert_eq!(&record.sequence()[..12], b"atgaaacccggg");
+        let cds = &record.features()[1];
+        assert_eq!(cds.location.to_string(), "complement(join(1..10,21..40))");
+        assert_eq!(
+            cds.qualifiers,
+            vec![
+                ("locus_tag".to_string(), "T_1".to_string()),
+                (
+                    "note".to_string(),
+                    "a \"quoted\" note that runs over two lines".to_string()
+                ),
+                ("translation".to_string(), "MKKKLLL".to_string()),
+                ("pseudo".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_the_same_whatever_the_buffer_size() {
+        let text = format!("{}{}", RECORD, RECORD.replace("NC_1 ", "NC_2 "));
+        let expected: Vec<String> = Reader::from_bufread(text.as_bytes())
+            .records()
+            .map(|record| format!("{:?}", record.unwrap()))
+            .collect();
+        assert_eq!(expected.len(), 2);
+        for capacity in [1, 3, 16, 100] {
+            let reader = io::BufReader::with_capacity(capacity, text.as_bytes());
+            let records: Vec<String> = Reader::from_bufread(reader)
+                .records()
+                .map(|record| format!("{:?}", record.unwrap()))
+                .collect();
+            assert_eq!(records, expected, "capacity {}", capacity);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for text in ["LOCUS\n//\n", "LOCUS       NC_1  many bp\n//\n"] {
+            let error = Reader::from_bufread(text.as_bytes())
+                .records()
+                .next()
+                .unwrap()
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().starts_with("Malformed LOCUS line"));
+        }
+        let text = b"LOCUS       NC_1  3 bp\nDEFINITION  caf\xe9\n//\n";
+        assert!(Reader::from_bufread(&text[..])
+            .records()
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn writes_the_standard_locus_line() {
+        let mut record = Record::new();
+        Reader::from_bufread(RECORD.as_bytes())
+            .read(&mut record)
+            .unwrap();
+        assert_eq!((record.division(), record.date()), ("BCT", "01-JAN-2020"));
+        let mut written = Vec::new();
+        write(&mut written, &record).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(written.lines().next(), RECORD.lines().next());
+        let mut again = Record::new();
+        Reader::from_bufread(written.as_bytes())
+            .read(&mut again)
+            .unwrap();
+        assert_eq!(format!("{:?}", again), format!("{:?}", record));
+
+        let sliced = record.slice(0, 20).unwrap();
+        let mut written = Vec::new();
+        write(&mut written, &sliced).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(
+            written.lines().next().unwrap(),
+            "LOCUS       NC_1_1-20                 20 bp    DNA     linear   BCT 01-JAN-2020"
+        );
+        assert!(!written.contains("VERSION"));
+
+        let rna = Record {
+            id: "X1".to_string(),
+            molecule: "ss-RNA".to_string(),
+            sequence: b"acg".to_vec(),
+            ..Record::default()
+        };
+        let mut written = Vec::new();
+        write(&mut written, &rna).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap().lines().next().unwrap(),
+            "LOCUS       X1                         3 bp ss-RNA     linear   UNA 01-JAN-1980"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::record::{Feature, Record};
+use std::str::FromStr;
+
+/// Piece of a parsed header template.
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Literal(String),
+    /// `{name}` or `{name:default}`.
+    Field {
+        name: String,
+        default: String,
+    },
+}
+
+/// A FASTA header template such as
+/// `{locus_tag}|{gene:-}|{product} [{organism}] {accession}:{start}-{end}({strand})`.
+///
+/// Fields are filled from the record (`id`, `accession`, `version`,
+/// `definition`, `organism`, `record_length`), from the feature (`type`,
+/// `start`, `end`, `strand`, `location`, `length`, and the unique
+/// `feature_id` chosen by the converter) and otherwise from the
+/// feature's qualifiers and then the record's `source` qualifiers. Missing
+/// values use the default after `:`, or are left empty. `{{` and `}}` write
+/// literal braces. On circular records `start` and `end` follow the written
+/// location, so a feature at `join(2900..3000,1..50)` runs from 2900 to 50.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("unclosed {{ in header template {:?}", s)),
+                        }
+                    }
+                    let (name, default) = field.split_once(':').unwrap_or((&field, ""));
+                    if name.trim().is_empty() {
+                        return Err(format!("empty field in header template {:?}", s));
+                    }
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field {
+                        name: name.trim().to_string(),
+                        default: default.to_string(),
+                    });
+                }
+                '}' => return Err(format!("unmatched }} in header template {:?}", s)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Template { pieces })
+    }
+}
+
+impl Template {
+    /// Fix every `{name}` field to `value`, leaving the others to `render`.
+    pub fn bind(&self, name: &str, value: &str) -> Template {
+        let pieces = self
+            .pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Field { name: field, .. } if field == name => {
+                    Piece::Literal(value.to_string())
+                }
+                piece => piece.clone(),
+            })
+            .collect();
+        Template { pieces }
+    }
+    /// True when the template has a `{name}` field for any of `names`.
+    pub fn uses(&self, names: &[&str]) -> bool {
+        self.pieces.iter().any(|piece| match piece {
+            Piece::Field { name, .. } => names.contains(&name.as_str()),
+            Piece::Literal(_) => false,
+        })
+    }
+    /// Fill the template for a whole record, or for one of its features.
+    pub fn render(&self, record: &Record, feature: Option<&Feature>) -> String {
+        self.render_inner(record, feature, None)
+    }
+    /// As `render`, also filling `{feature_id}` with the id the converter
+    /// resolved for the feature.
+    pub fn render_with_id(&self, record: &Record, feature: &Feature, feature_id: &str) -> String {
+        self.render_inner(record, Some(feature), Some(feature_id))
+    }
+    fn render_inner(
+        &self,
+        record: &Record,
+        feature: Option<&Feature>,
+        feature_id: Option<&str>,
+    ) -> String {
+        let mut header = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(text) => header.push_str(text),
+                Piece::Field { name, default } => match field(record, feature, feature_id, name) {
+                    Some(value) if !value.is_empty() => header.push_str(&value),
+                    _ => header.push_str(default),
+                },
+            }
+        }
+        header
+    }
+}
+
+fn field(
+    record: &Record,
+    feature: Option<&Feature>,
+    feature_id: Option<&str>,
+    name: &str,
+) -> Option<String> {
+    let value = match name {
+        "feature_id" => return feature_id.map(str::to_string),
+        "id" => record.id().to_string(),
+        "accession" => record.accession().to_string(),
+        "version" => record.version().to_string(),
+        "definition" => record.definition().to_string(),
+        "organism" => record.organism().to_string(),
+        "record_length" => record.length().to_string(),
+        _ => return feature_field(record, feature, name),
+    };
+    Some(value)
+}
+
+fn feature_field(record: &Record, feature: Option<&Feature>, name: &str) -> Option<String> {
+    let location = feature.map(|f| &f.location);
+    let value = match name {
+        "type" => feature
+            .map(|f| f.kind.clone())
+            .unwrap_or_else(|| "source".to_string()),
+        "start" => match location {
+            Some(l) if record.is_circular() => l.written_start() + 1,
+            Some(l) => l.start() + 1,
+            None => 1,
+        }
+        .to_string(),
+        "end" => match location {
+            Some(l) if record.is_circular() => l.written_end(),
+            Some(l) => l.end(),
+            None => record.sequence().len(),
+        }
+        .to_string(),
+        "strand" => match location.map_or(1, |l| l.strand()) {
+            -1 => "-".to_string(),
+            _ => "+".to_string(),
+        },
+        "location" => match location {
+            Some(l) => l.to_string(),
+            None => format!("1..{}", record.sequence().len()),
+        },
+        "length" => location
+            .map_or(record.sequence().len(), |l| l.len())
+            .to_string(),
+        _ => {
+            return feature
+                .and_then(|f| f.qualifier(name))
+                .or_else(|| record.source().and_then(|s| s.qualifier(name)))
+                .map(str::to_string)
+        }
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+
+    fn feature(kind: &str, location: &str, qualifiers: &[(&str, &str)]) -> Feature {
+        Feature {
+            kind: kind.to_string(),
+            location: Location::parse(location).unwrap(),
+            qualifiers: qualifiers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    fn record(circular: bool) -> Record {
+        Record {
+            id: "NC_1".to_string(),
+            length: 3000,
+            circular,
+            accession: "NC_1".to_string(),
+            version: "NC_1.2".to_string(),
+            organism: "Escherichia coli".to_string(),
+            sequence: vec![b'A'; 3000],
+            features: vec![feature("source", "1..3000", &[("strain", "K-12")])],
+            ..Record::default()
+        }
+    }
+
+    fn render(template: &str, record: &Record, feature: Option<&Feature>) -> String {
+        template
+            .parse::<Template>()
+            .unwrap()
+            .render(record, feature)
+    }
+
+    #[test]
+    fn rejects_unbalanced_braces_and_empty_fields() {
+        for (template, error) in [
+            ("{locus_tag", "unclosed {"),
+            ("locus_tag}", "unmatched }"),
+            ("{}", "empty field"),
+            ("{:x}", "empty field"),
+        ] {
+            let message = template.parse::<Template>().unwrap_err();
+            assert!(message.contains(error), "{}", message);
+        }
+    }
+
+    #[test]
+    fn fills_record_feature_and_source_fields() {
+        let record = record(false);
+        let cds = feature(
+            "CDS",
+            "complement(join(101..200,301..400))",
+            &[("locus_tag", "T_1"), ("gene", "")],
+        );
+        assert_eq!(
+            render(
+                "{locus_tag}|{gene:-}|{product:none} {type} {accession}:{start}-{end}({strand}) {length} {location}",
+                &record,
+                Some(&cds)
+            ),
+            "T_1|-|none CDS NC_1:101-400(-) 200 complement(join(101..200,301..400))"
+        );
+        assert_eq!(
+            render(
+                "{version} [{organism}] strain={strain} {record_length}",
+                &record,
+                Some(&cds)
+            ),
+            "NC_1.2 [Escherichia coli] strain=K-12 3000"
+        );
+        assert_eq!(
+            render("{type} {start}-{end}({strand}) {location}", &record, None),
+            "source 1-3000(+) 1..3000"
+        );
+    }
+
+    #[test]
+    fn writes_literal_braces() {
+        assert_eq!(render("{{{id}}}", &record(false), None), "{NC_1}");
+    }
+
+    #[test]
+    fn binds_fields_and_the_feature_id() {
+        let record = record(false);
+        let cds = feature("CDS", "101..200", &[("locus_tag", "T_1")]);
+        let template: Template = "{file}/{feature_id} {locus_tag}".parse().unwrap();
+        assert!(template.uses(&["feature_id", "x"]));
+        assert!(!template.uses(&["gene"]));
+        let bound = template.bind("file", "a.fna");
+        assert!(!bound.uses(&["file"]));
+        assert_eq!(
+            bound.render_with_id(&record, &cds, "T_1_2"),
+            "a.fna/T_1_2 T_1"
+        );
+        assert_eq!(bound.render(&record, Some(&cds)), "a.fna/ T_1");
+    }
+
+    #[test]
+    fn follows_the_written_location_across_the_origin() {
+        for (location, span) in [
+            ("join(2951..3000,1..49)", "2951-49"),
+            ("complement(join(2951..3000,1..49))", "2951-49"),
+            ("join(101..200,301..400)", "101-400"),
+        ] {
+            let cds = feature("CDS", location, &[]);
+            assert_eq!(render("{start}-{end}", &record(true), Some(&cds)), span);
+        }
+        let cds = feature("CDS", "join(2951..3000,1..49)", &[]);
+        assert_eq!(
+            render("{start}-{end}", &record(false), Some(&cds)),
+            "1-3000"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::record::Record;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// Qualifiers tried in order to name a feature, ending in a synthetic
+/// `{record}_feature{N}` id when none of them is present.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdChain {
+    keys: Vec<String>,
+}
+
+impl Default for IdChain {
+    fn default() -> Self {
+        IdChain {
+            keys: vec![
+                "locus_tag".to_string(),
+                "protein_id".to_string(),
+                "gene".to_string(),
+            ],
+        }
+    }
+}
+
+impl FromStr for IdChain {
+    type Err = String;
+
+    /// A comma separated list of qualifiers, e.g. `locus_tag,protein_id,gene`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys: Vec<String> = s
+            .split(',')
+            .map(|key| key.trim().trim_start_matches('/').to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+        if keys.is_empty() {
+            return Err("empty identifier chain".to_string());
+        }
+        Ok(IdChain { keys })
+    }
+}
+
+impl IdChain {
+    /// The first qualifier in the chain present on the feature at `index`
+    /// of the record's feature table, otherwise `{record}_feature{N}` with
+    /// N = `index` + 1.
+    pub fn base_id(&self, record: &Record, index: usize) -> String {
+        let feature = &record.features()[index];
+        for key in &self.keys {
+            if let Some(value) = feature.qualifier(key).filter(|v| !v.trim().is_empty()) {
+                return value.trim().to_string();
+            }
+        }
+        format!("{}_feature{}", record.id(), index + 1)
+    }
+}
+
+/// Hands out each id once; repeats get `_2`, `_3`, ... appended.
+#[derive(Default, Debug)]
+pub struct UniqueIds {
+    used: HashSet<String>,
+    next_suffix: HashMap<String, usize>,
+}
+
+impl UniqueIds {
+    pub fn new() -> Self {
+        UniqueIds::default()
+    }
+    pub fn unique(&mut self, id: String) -> String {
+        if self.used.insert(id.clone()) {
+            return id;
+        }
+        let suffix = self.next_suffix.entry(id.clone()).or_insert(2);
+        loop {
+            let candidate = format!("{}_{}", id, suffix);
+            *suffix += 1;
+            if self.used.insert(candidate.clone()) {
+                return candidate;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+    use crate::record::Feature;
+
+    fn record() -> Record {
+        let feature = |qualifiers: &[(&str, &str)]| Feature {
+            kind: "CDS".to_string(),
+            location: Location::parse("1..9").unwrap(),
+            qualifiers: qualifiers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        Record {
+            id: "NC_1".to_string(),
+            features: vec![
+                feature(&[("gene", "abcD"), ("locus_tag", "T_1")]),
+                feature(&[("locus_tag", " "), ("protein_id", "WP_1.1")]),
+                feature(&[("gene", "abcD")]),
+                feature(&[("note", "no name")]),
+            ],
+            ..Record::default()
+        }
+    }
+
+    #[test]
+    fn parses_comma_separated_qualifiers() {
+        assert_eq!(
+            " /gene, locus_tag ,".parse::<IdChain>(),
+            Ok(IdChain {
+                keys: vec!["gene".to_string(), "locus_tag".to_string()]
+            })
+        );
+        assert_eq!(
+            "locus_tag,protein_id,gene".parse::<IdChain>(),
+            Ok(IdChain::default())
+        );
+        assert!(" , ".parse::<IdChain>().is_err());
+    }
+
+    #[test]
+    fn falls_back_through_the_chain() {
+        let record = record();
+        let ids: Vec<String> = (0..4)
+            .map(|i| IdChain::default().base_id(&record, i))
+            .collect();
+        assert_eq!(ids, vec!["T_1", "WP_1.1", "abcD", "NC_1_feature4"]);
+        let gene_first: IdChain = "gene,locus_tag".parse().unwrap();
+        assert_eq!(gene_first.base_id(&record, 0), "abcD");
+        assert_eq!(gene_first.base_id(&record, 1), "NC_1_feature2");
+    }
+
+    #[test]
+    fn suffixes_repeated_ids() {
+        let mut ids = UniqueIds::new();
+        let given: Vec<String> = ["a", "a", "a_2", "a", "b", "a_3"]
+            .iter()
+            .map(|id| ids.unique(id.to_string()))
+            .collect();
+        assert_eq!(given, vec!["a", "a_2", "a_2_2", "a_3", "b", "a_3_2"]);
+    }
+}
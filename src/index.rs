@@ -0,0 +1,698 @@
+use crate::bgzf::{self, Gzi};
+use crate::compression::{self, Compression};
+use crate::gbk::GbkRead;
+use crate::input::Format;
+use crate::lines::{self, LineReader};
+use crate::record::Record;
+use crate::sink::AtomicFile;
+use crate::{embl, gbk};
+use anyhow::Context;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Columns of an index file.
+pub const HEADER: &str =
+    "#id\taccession\tversion\tformat\toffset\tlength\tsequence_offset\tbases\tline_bases\tline_bytes";
+
+/// Where one record lies in a flat file, like a line of a `.fai`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub id: String,
+    pub accession: String,
+    pub version: String,
+    pub format: Format,
+    /// Byte offset of the `LOCUS` or `ID` line.
+    pub offset: u64,
+    /// Bytes up to and including the `//` line.
+    pub length: u64,
+    /// Byte offset of the first line after `ORIGIN` or `SQ`.
+    pub sequence_offset: u64,
+    pub bases: u64,
+    /// Bases on every full sequence line, or 0 when the lines vary and the
+    /// whole sequence has to be read to find a range.
+    pub line_bases: u64,
+    pub line_bytes: u64,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Entry> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 10 {
+            return None;
+        }
+        let number = |i: usize| fields[i].parse::<u64>().ok();
+        Some(Entry {
+            id: fields[0].to_string(),
+            accession: fields[1].to_string(),
+            version: fields[2].to_string(),
+            format: fields[3].parse().ok()?,
+            offset: number(4)?,
+            length: number(5)?,
+            sequence_offset: number(6)?,
+            bases: number(7)?,
+            line_bases: number(8)?,
+            line_bytes: number(9)?,
+        })
+    }
+}
+
+/// Record offsets of a flat file, looked up by id, accession or
+/// `accession.version`.
+#[derive(Clone, Debug, Default)]
+pub struct Index {
+    entries: Vec<Entry>,
+    names: HashMap<String, usize>,
+}
+
+/// Path of the index kept next to `path`.
+pub fn index_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".idx");
+    PathBuf::from(name)
+}
+
+impl Index {
+    fn new(entries: Vec<Entry>) -> Index {
+        let mut names = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for name in [&entry.id, &entry.accession, &entry.version] {
+                if !name.is_empty() {
+                    names.entry(name.clone()).or_insert(i);
+                }
+            }
+        }
+        Index { entries, names }
+    }
+
+    /// Scan an uncompressed flat file for its records.
+    pub fn build<R: BufRead>(reader: R) -> io::Result<Index> {
+        let mut builder = Builder::default();
+        let mut lines = LineReader::new(reader);
+        while let Some(line) = lines.next_line()? {
+            builder.push_line(line)?;
+        }
+        Ok(Index::new(builder.finish()))
+    }
+
+    /// Index the uncompressed or BGZF-compressed file at `path`. Offsets are
+    /// always into the uncompressed bytes.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Index> {
+        let path = path.as_ref();
+        let compression = detect(path)?;
+        anyhow::ensure!(
+            matches!(compression, Compression::None | Compression::Bgzf),
+            "Cannot index {:?}: {} input has to be decompressed or recompressed with bgzip first",
+            path,
+            compression
+        );
+        Index::build(compression::open(path)?)
+            .with_context(|| format!("Failed to index {:?}", path))
+    }
+
+    /// Read an index written by `write`.
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Index> {
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let entry = Entry::parse(&line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Malformed index line: {}", line),
+                )
+            })?;
+            entries.push(entry);
+        }
+        Ok(Index::new(entries))
+    }
+
+    /// Read the index kept next to `path`, refusing one older than the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Index> {
+        let path = path.as_ref();
+        let index = index_path(path);
+        let file = fs::File::open(&index)
+            .with_context(|| format!("Failed to open {:?}; run annots index {:?}", index, path))?;
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        if let (Some(data), Some(indexed)) = (modified(path), modified(&index)) {
+            anyhow::ensure!(
+                indexed >= data,
+                "{:?} is older than {:?}; run annots index again",
+                index,
+                path
+            );
+        }
+        Index::read(io::BufReader::new(file)).with_context(|| format!("Failed to read {:?}", index))
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        for e in &self.entries {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                e.id,
+                e.accession,
+                e.version,
+                e.format,
+                e.offset,
+                e.length,
+                e.sequence_offset,
+                e.bases,
+                e.line_bases,
+                e.line_bytes
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the index next to `path`, replacing any older one.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = AtomicFile::create(index_path(path.as_ref()))?;
+        self.write(&mut file)?;
+        file.commit()
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The record called `name` by its id, accession or version.
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.names.get(name).map(|&i| &self.entries[i])
+    }
+}
+
+fn detect(path: &Path) -> anyhow::Result<Compression> {
+    let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut reader = io::BufReader::new(file);
+    let head = reader
+        .fill_buf()
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(Compression::detect(head))
+}
+
+/// Index `path`, writing `<path>.idx` and, for a BGZF file, `<path>.gzi`
+/// next to it.
+pub fn create<P: AsRef<Path>>(path: P) -> anyhow::Result<Index> {
+    let path = path.as_ref();
+    let index = Index::from_file(path)?;
+    if detect(path)? == Compression::Bgzf {
+        Gzi::from_file(path)?
+            .save(path)
+            .with_context(|| format!("Failed to write the block index of {:?}", path))?;
+    }
+    index
+        .save(path)
+        .with_context(|| format!("Failed to write the index of {:?}", path))?;
+    Ok(index)
+}
+
+/// Bases and bytes per sequence line, given up on once lines disagree.
+#[derive(Default)]
+struct Layout {
+    lines: u64,
+    line_bases: u64,
+    line_bytes: u64,
+    bases: u64,
+    regular: bool,
+    /// A line shorter than the first was seen, so it must be the last.
+    ended: bool,
+}
+
+impl Layout {
+    fn push(&mut self, bases: u64, bytes: u64) {
+        if self.lines == 0 {
+            self.line_bases = bases;
+            self.line_bytes = bytes;
+            self.regular = bases > 0;
+        } else if self.ended
+            || bases > self.line_bases
+            || (bases == self.line_bases && bytes != self.line_bytes)
+        {
+            self.regular = false;
+        } else if bases < self.line_bases {
+            self.ended = true;
+        }
+        self.lines += 1;
+        self.bases += bases;
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    entries: Vec<Entry>,
+    current: Option<Entry>,
+    layout: Option<Layout>,
+    offset: u64,
+}
+
+impl Builder {
+    fn push_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let start = self.offset;
+        self.offset += line.len() as u64;
+        if line.starts_with(b"//") {
+            self.finish_record(self.offset);
+            return Ok(());
+        }
+        if let (Some(layout), Some(entry)) = (self.layout.as_mut(), self.current.as_ref()) {
+            let bases = match entry.format {
+                Format::Embl => lines::before_last_word(line),
+                _ => lines::after_first_word(line),
+            };
+            let bases = bases.iter().filter(|b| !b.is_ascii_whitespace()).count();
+            layout.push(bases as u64, line.len() as u64);
+            return Ok(());
+        }
+        let text = lines::text(line)?;
+        let format = if text.starts_with("LOCUS") {
+            Some(Format::Genbank)
+        } else if text.starts_with("ID   ") {
+            Some(Format::Embl)
+        } else {
+            None
+        };
+        if let Some(format) = format {
+            self.finish_record(start);
+            self.current = Some(header(text, format, start));
+            return Ok(());
+        }
+        let entry = match self.current.as_mut() {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        let first_word = |value: &str| {
+            value
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        };
+        match entry.format {
+            Format::Embl => {
+                if let Some(value) = text.strip_prefix("AC   ") {
+                    if entry.accession.is_empty() {
+                        entry.accession = value
+                            .split(';')
+                            .next()
+                            .unwrap_or_default()
+                            .trim()
+                            .to_string();
+                    }
+                } else if text.starts_with("SQ") {
+                    entry.sequence_offset = self.offset;
+                    self.layout = Some(Layout::default());
+                }
+            }
+            _ => {
+                let value = text.get(12..).unwrap_or_default();
+                if text.starts_with("ACCESSION") {
+                    entry.accession = first_word(value);
+                } else if text.starts_with("VERSION") {
+                    entry.version = first_word(value);
+                } else if text.starts_with("ORIGIN") {
+                    entry.sequence_offset = self.offset;
+                    self.layout = Some(Layout::default());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish_record(&mut self, end: u64) {
+        let mut entry = match self.current.take() {
+            Some(entry) => entry,
+            None => return,
+        };
+        entry.length = end - entry.offset;
+        if let Some(layout) = self.layout.take() {
+            entry.bases = layout.bases;
+            if layout.regular {
+                entry.line_bases = layout.line_bases;
+                entry.line_bytes = layout.line_bytes;
+            }
+        } else {
+            entry.sequence_offset = end;
+        }
+        self.entries.push(entry);
+    }
+
+    fn finish(mut self) -> Vec<Entry> {
+        self.finish_record(self.offset);
+        self.entries
+    }
+}
+
+/// An entry started from a `LOCUS` or `ID` line.
+fn header(line: &str, format: Format, offset: u64) -> Entry {
+    let mut entry = Entry {
+        id: String::new(),
+        accession: String::new(),
+        version: String::new(),
+        format,
+        offset,
+        length: 0,
+        sequence_offset: 0,
+        bases: 0,
+        line_bases: 0,
+        line_bytes: 0,
+    };
+    match format {
+        Format::Embl => {
+            let mut fields = line.split(';');
+            entry.id = fields
+                .next()
+                .and_then(|id| id.split_whitespace().nth(1))
+                .unwrap_or_default()
+                .to_string();
+            let version = fields.next().map(str::trim);
+            if let Some(sv) = version.and_then(|sv| sv.strip_prefix("SV ")) {
+                entry.version = format!("{}.{}", entry.id, sv.trim());
+            }
+        }
+        _ => {
+            entry.id = line
+                .split_whitespace()
+                .nth(1)
+                .unwrap_or_default()
+                .to_string();
+        }
+    }
+    entry
+}
+
+/// Fetches single records, or ranges of their sequence, from an indexed
+/// flat file without reading the rest of it.
+pub struct IndexedReader<R> {
+    reader: R,
+    index: Index,
+}
+
+/// A flat file opened for random access, plain or BGZF-compressed.
+pub enum Source {
+    Plain(io::BufReader<fs::File>),
+    Bgzf(bgzf::Reader<io::BufReader<fs::File>>),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::Plain(reader) => reader.read(buf),
+            Source::Bgzf(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::Plain(reader) => reader.seek(position),
+            Source::Bgzf(reader) => reader.seek(position),
+        }
+    }
+}
+
+impl IndexedReader<Source> {
+    /// Open `path` with the index kept next to it, and its `.gzi` when the
+    /// file is BGZF-compressed.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let index = Index::load(path)?;
+        let source = match detect(path)? {
+            Compression::None => {
+                let file =
+                    fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+                Source::Plain(io::BufReader::new(file))
+            }
+            Compression::Bgzf => Source::Bgzf(bgzf::Reader::from_file(path)?),
+            compression => anyhow::bail!(
+                "Cannot fetch from {:?}: {} input has no random access; recompress it with bgzip",
+                path,
+                compression
+            ),
+        };
+        Ok(IndexedReader::new(source, index))
+    }
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    pub fn new(reader: R, index: Index) -> Self {
+        IndexedReader { reader, index }
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    fn entry(&self, name: &str) -> io::Result<Entry> {
+        self.index.get(name).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No record named {:?} in the index", name),
+            )
+        })
+    }
+
+    fn read_at(&mut self, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; length as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// The record as it is written in the file.
+    pub fn fetch_raw(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        let entry = self.entry(name)?;
+        self.read_at(entry.offset, entry.length)
+    }
+
+    pub fn fetch(&mut self, name: &str) -> io::Result<Record> {
+        let format = self.entry(name)?.format;
+        let bytes = self.fetch_raw(name)?;
+        let mut record = Record::new();
+        match format {
+            Format::Embl => embl::Reader::from_bufread(&bytes[..]).read(&mut record)?,
+            _ => gbk::Reader::from_bufread(&bytes[..]).read(&mut record)?,
+        }
+        Ok(record)
+    }
+
+    /// Bases `start..end`, 0-based and end-exclusive, of the record `name`.
+    /// Only the sequence lines holding them are read when the lines are
+    /// regular.
+    pub fn fetch_sequence(&mut self, name: &str, start: u64, end: u64) -> io::Result<Vec<u8>> {
+        let entry = self.entry(name)?;
+        if start > end || end > entry.bases {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}:{}-{} is outside the {} bases of the record",
+                    name,
+                    start + 1,
+                    end,
+                    entry.bases
+                ),
+            ));
+        }
+        let record_end = entry.offset + entry.length;
+        let first = start.checked_div(entry.line_bases);
+        let last = end
+            .saturating_sub(1)
+            .max(start)
+            .checked_div(entry.line_bases);
+        let (from, skip, to) = match (first, last) {
+            (Some(first), Some(last)) => (
+                entry.sequence_offset + first * entry.line_bytes,
+                start - first * entry.line_bases,
+                entry.sequence_offset + (last + 1) * entry.line_bytes,
+            ),
+            // irregular lines, so the whole sequence is read
+            _ => (entry.sequence_offset, start, record_end),
+        };
+        let bytes = self.read_at(from, to.min(record_end) - from)?;
+        let mut sequence = Vec::new();
+        for line in bytes.split(|&b| b == b'\n') {
+            if line.starts_with(b"//") {
+                break;
+            }
+            let bases = match entry.format {
+                Format::Embl => lines::before_last_word(line),
+                _ => lines::after_first_word(line),
+            };
+            lines::push_bases(&mut sequence, bases);
+        }
+        let (skip, wanted) = (skip as usize, (end - start) as usize);
+        if sequence.len() < skip + wanted {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Sequence of {} is shorter than its index says", name),
+            ));
+        }
+        sequence.drain(..skip);
+        sequence.truncate(wanted);
+        Ok(sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::BgzfWriter;
+    use std::io::Cursor;
+
+    /// A GenBank record whose sequence lines hold `widths` bases each.
+    fn genbank(name: &str, sequence: &[u8], widths: &[usize]) -> Vec<u8> {
+        let mut text = format!(
+            "LOCUS       {}  {} bp    DNA     linear   BCT 01-JAN-2020\nACCESSION   {}\nVERSION     {}.1\nORIGIN\n",
+            name,
+            sequence.len(),
+            name,
+            name
+        );
+        let mut position = 0;
+        for &width in widths {
+            let line = &sequence[position..position + width];
+            let words: Vec<&str> = line
+                .chunks(10)
+                .map(|word| std::str::from_utf8(word).unwrap())
+                .collect();
+            text.push_str(&format!("{:>9} {}\n", position + 1, words.join(" ")));
+            position += width;
+        }
+        text.push_str("//\n");
+        text.into_bytes()
+    }
+
+    fn sequence(length: usize) -> Vec<u8> {
+        (0..length).map(|i| b"acgt"[i * 7 % 11 % 4]).collect()
+    }
+
+    fn check<R: Read + Seek>(reader: &mut IndexedReader<R>, name: &str, sequence: &[u8]) {
+        for (start, end) in [
+            (0, 1),
+            (0, 60),
+            (59, 61),
+            (60, 120),
+            (100, 250),
+            (0, 250),
+            (249, 250),
+            (7, 7),
+        ] {
+            assert_eq!(
+                reader.fetch_sequence(name, start, end).unwrap(),
+                &sequence[start as usize..end as usize],
+                "{}:{}-{}",
+                name,
+                start + 1,
+                end
+            );
+        }
+        assert!(reader.fetch_sequence(name, 0, 251).is_err());
+    }
+
+    #[test]
+    fn fetches_ranges_of_regular_and_irregular_sequences() {
+        let regular = sequence(250);
+        let irregular = sequence(250);
+        let mut file = genbank("REG1", &regular, &[60, 60, 60, 60, 10]);
+        file.extend(genbank("IRR1", &irregular, &[60, 50, 70, 60, 10]));
+        let index = Index::build(&file[..]).unwrap();
+        assert_eq!(index.get("REG1").unwrap().line_bases, 60);
+        assert_eq!(index.get("IRR1.1").unwrap().line_bases, 0);
+        let mut reader = IndexedReader::new(Cursor::new(file.clone()), index.clone());
+        check(&mut reader, "REG1", &regular);
+        check(&mut reader, "IRR1", &irregular);
+
+        let mut writer = BgzfWriter::new(Vec::new());
+        writer.write_all(&file).unwrap();
+        let compressed = writer.finish().unwrap();
+        let gzi = Gzi::build(&compressed[..]).unwrap();
+        let mut reader = IndexedReader::new(bgzf::Reader::new(Cursor::new(compressed), gzi), index);
+        check(&mut reader, "REG1", &regular);
+        check(&mut reader, "IRR1", &irregular);
+    }
+
+    #[test]
+    fn finds_records_by_id_accession_or_version() {
+        let (first, second) = (
+            genbank("REC1", &sequence(70), &[60, 10]),
+            genbank("REC2", &sequence(30), &[30]),
+        );
+        let file = [first.clone(), second.clone()].concat();
+        let index = Index::build(&file[..]).unwrap();
+        let entry = index.get("REC2.1").unwrap();
+        assert_eq!(
+            (entry.offset, entry.length),
+            (first.len() as u64, second.len() as u64)
+        );
+        assert_eq!((entry.bases, entry.line_bases), (30, 30));
+        assert_eq!(index.get("REC2"), Some(entry));
+        assert_eq!(index.get("REC1").unwrap().offset, 0);
+        assert!(index.get("REC3").is_none());
+        let mut reader = IndexedReader::new(Cursor::new(file), index);
+        assert_eq!(reader.fetch_raw("REC2").unwrap(), second);
+        let record = reader.fetch("REC1.1").unwrap();
+        assert_eq!(record.sequence(), &sequence(70)[..]);
+        let error = reader.fetch("REC3").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(error.to_string(), "No record named \"REC3\" in the index");
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let file = [
+            genbank("REC1", &sequence(70), &[60, 10]),
+            genbank("REC2", &sequence(30), &[30]),
+        ]
+        .concat();
+        let index = Index::build(&file[..]).unwrap();
+        let mut written = Vec::new();
+        index.write(&mut written).unwrap();
+        assert!(written.starts_with(HEADER.as_bytes()));
+        let read = Index::read(&written[..]).unwrap();
+        assert_eq!(read.entries(), index.entries());
+        assert_eq!(read.get("REC2.1"), index.get("REC2.1"));
+        let error = Index::read(&b"REC1\tREC1\n"[..]).unwrap_err();
+        assert_eq!(error.to_string(), "Malformed index line: REC1\tREC1");
+    }
+
+    #[test]
+    fn refuses_stale_indexes_and_plain_gzip() {
+        let dir = std::env::temp_dir().join(format!("annots-index-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("records.gbk");
+        fs::write(&path, genbank("REC1", &sequence(70), &[60, 10])).unwrap();
+        create(&path).unwrap();
+        let mut reader = IndexedReader::from_file(&path).unwrap();
+        assert_eq!(
+            reader.fetch_sequence("REC1", 58, 62).unwrap(),
+            &sequence(70)[58..62]
+        );
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let error = IndexedReader::from_file(&path).err().unwrap().to_string();
+        assert!(error.ends_with("run annots index again"), "{}", error);
+
+        let gzipped = dir.join("records.gbk.gz");
+        let mut sink = crate::sink::Sink::create(&gzipped, Compression::Gzip).unwrap();
+        sink.write_all(&genbank("REC1", &sequence(70), &[60, 10]))
+            .unwrap();
+        sink.finish().unwrap();
+        let error = Index::from_file(&gzipped).unwrap_err().to_string();
+        assert!(
+            error.contains("gzip input has to be decompressed or recompressed"),
+            "{}",
+            error
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,303 @@
+use crate::compression;
+use crate::record::Record;
+use crate::{embl, gbk};
+use anyhow::Context;
+use regex::Regex;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Sequence file formats that can be recognised from their first bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Genbank,
+    Embl,
+    /// GenBank flat file of protein records, read with the GenBank reader.
+    Genpept,
+    SwissProt,
+    Fasta,
+    Fastq,
+    Gff3,
+    InsdseqXml,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Format::Genbank => "genbank",
+            Format::Embl => "embl",
+            Format::Genpept => "genpept",
+            Format::SwissProt => "swissprot",
+            Format::Fasta => "fasta",
+            Format::Fastq => "fastq",
+            Format::Gff3 => "gff3",
+            Format::InsdseqXml => "insdseq-xml",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "genbank" | "gbk" | "gb" | "gbff" => Ok(Format::Genbank),
+            "embl" => Ok(Format::Embl),
+            "genpept" | "gp" | "gpff" => Ok(Format::Genpept),
+            "swissprot" | "swiss" | "uniprot" => Ok(Format::SwissProt),
+            "fasta" | "fa" => Ok(Format::Fasta),
+            "fastq" | "fq" => Ok(Format::Fastq),
+            "gff3" | "gff" => Ok(Format::Gff3),
+            "insdseq-xml" | "insdseq" | "xml" => Ok(Format::InsdseqXml),
+            _ => Err(format!("unknown input format {:?}", s)),
+        }
+    }
+}
+
+impl Format {
+    /// Recognise a format from the start of a file, ignoring leading blank
+    /// lines.
+    pub fn detect(head: &[u8]) -> Option<Format> {
+        let start = head.iter().position(|c| !c.is_ascii_whitespace())?;
+        let head = &head[start..];
+        let first_line = head.split(|&c| c == b'\n').next().unwrap_or_default();
+        let first_line = String::from_utf8_lossy(first_line);
+        if first_line.starts_with("LOCUS ") {
+            let protein = first_line.split_whitespace().any(|field| field == "aa");
+            return Some(if protein {
+                Format::Genpept
+            } else {
+                Format::Genbank
+            });
+        }
+        if first_line.starts_with("ID   ") {
+            let protein = first_line.trim_end().ends_with("AA.");
+            return Some(if protein {
+                Format::SwissProt
+            } else {
+                Format::Embl
+            });
+        }
+        if first_line.starts_with("##gff-version 3") {
+            return Some(Format::Gff3);
+        }
+        if head.starts_with(b"<?xml") || head.starts_with(b"<INSDSet") {
+            let text = String::from_utf8_lossy(head);
+            return if text.contains("<INSDSet") || text.contains("<INSDSeq") {
+                Some(Format::InsdseqXml)
+            } else {
+                None
+            };
+        }
+        match head[0] {
+            b'>' => Some(Format::Fasta),
+            b'@' => Some(Format::Fastq),
+            _ => None,
+        }
+    }
+
+    /// Whether the records can be read into annotated `Record`s.
+    pub fn has_reader(&self) -> bool {
+        matches!(self, Format::Genbank | Format::Embl | Format::Genpept)
+    }
+}
+
+/// Peek at the buffered start of `reader` to recognise its format, without
+/// consuming anything.
+pub fn detect<R: BufRead>(reader: &mut R) -> io::Result<Option<Format>> {
+    Ok(Format::detect(reader.fill_buf()?))
+}
+
+/// Boxed iterator over the records of an input of any readable format.
+pub type RecordIter = Box<dyn Iterator<Item = io::Result<Record>>>;
+
+/// Open `path` with the reader for `format`.
+pub fn open<P: AsRef<Path>>(path: P, format: Format) -> anyhow::Result<RecordIter> {
+    let path = path.as_ref();
+    records(compression::open(path)?, format).with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// Open `path` with the reader for whatever format its first bytes show,
+/// after any decompression.
+pub fn open_any<P: AsRef<Path>>(path: P) -> anyhow::Result<RecordIter> {
+    let path = path.as_ref();
+    let mut reader = compression::open(path)?;
+    let format = detect(&mut reader)
+        .with_context(|| format!("Failed to read {:?}", path))?
+        .with_context(|| format!("Could not recognise the format of {:?}", path))?;
+    records(reader, format).with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// Read records of `format` from an already opened, decompressed `reader`.
+pub fn records<B: BufRead + 'static>(reader: B, format: Format) -> anyhow::Result<RecordIter> {
+    Ok(match format {
+        Format::Genbank | Format::Genpept => Box::new(gbk::Reader::from_bufread(reader).records()),
+        Format::Embl => Box::new(embl::Reader::from_bufread(reader).records()),
+        _ => anyhow::bail!("{} input has no feature table reader", format),
+    })
+}
+
+/// Expand input arguments into files: directories are walked for files in a
+/// readable format, and arguments that are not existing paths are treated as
+/// glob patterns. Files named explicitly are kept whatever their format.
+pub fn expand<P: AsRef<Path>>(inputs: &[P]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let input = input.as_ref();
+        if input.is_dir() {
+            walk(input, &mut files)?;
+        } else if input.exists() {
+            files.push(input.to_path_buf());
+        } else {
+            let pattern = input.to_string_lossy();
+            let matches = glob::glob(&pattern)
+                .with_context(|| format!("Invalid input pattern {:?}", pattern))?;
+            let start = files.len();
+            for path in matches {
+                let path = path?;
+                if path.is_dir() {
+                    walk(&path, &mut files)?;
+                } else {
+                    files.push(path);
+                }
+            }
+            if files.len() == start {
+                anyhow::bail!("No input files match {:?}", pattern);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to list {:?}", dir))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if sniff(&path)?.is_some_and(|format| format.has_reader()) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Format of the file at `path`, after any decompression.
+pub fn sniff<P: AsRef<Path>>(path: P) -> anyhow::Result<Option<Format>> {
+    let path = path.as_ref();
+    let mut reader = compression::open(path)?;
+    detect(&mut reader).with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// File name without compression and flat file extensions, e.g. `NC_000913`
+/// for `NC_000913.gbff.gz`.
+pub fn file_stem(path: &Path) -> String {
+    let mut name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for extensions in [
+        &[".gz", ".bgz", ".bz2", ".xz", ".zst"][..],
+        &[".gbff", ".gbk", ".gb", ".genbank", ".gpff", ".embl", ".dat"][..],
+    ] {
+        if let Some(extension) = extensions.iter().find(|e| name.ends_with(*e)) {
+            name.truncate(name.len() - extension.len());
+        }
+    }
+    name
+}
+
+/// Assembly accession (`GCF_000005845.2`) in the file name, otherwise the
+/// file stem.
+pub fn assembly(path: &Path) -> String {
+    let stem = file_stem(path);
+    let accession = Regex::new(r"GC[AF]_\d+\.\d+").expect("valid regex");
+    match accession.find(&stem) {
+        Some(found) => found.as_str().to_string(),
+        None => stem,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_formats_from_their_first_line() {
+        for (head, format) in [
+            (
+                &b"LOCUS       NC_000913 4641652 bp    DNA     circular CON 09-MAR-2022\n"[..],
+                Some(Format::Genbank),
+            ),
+            (
+                b"\n\nLOCUS       NP_414542 21 aa            linear   CON 09-MAR-2022\n",
+                Some(Format::Genpept),
+            ),
+            (
+                b"ID   X56734; SV 1; linear; mRNA; STD; PLN; 1859 BP.\n",
+                Some(Format::Embl),
+            ),
+            (
+                b"ID   001R_FRG3G              Reviewed;         256 AA.\n",
+                Some(Format::SwissProt),
+            ),
+            (b"##gff-version 3\n", Some(Format::Gff3)),
+            (
+                b"<?xml version=\"1.0\"?>\n<INSDSet><INSDSeq>",
+                Some(Format::InsdseqXml),
+            ),
+            (b"<?xml version=\"1.0\"?>\n<html>", None),
+            (b">T_1\nATG\n", Some(Format::Fasta)),
+            (b"@read\nATG\n+\nIII\n", Some(Format::Fastq)),
+            (b"LOCUS", None),
+            (b"  \n", None),
+            (b"", None),
+        ] {
+            assert_eq!(
+                Format::detect(head),
+                format,
+                "{}",
+                String::from_utf8_lossy(head)
+            );
+        }
+    }
+
+    #[test]
+    fn only_flat_files_have_readers() {
+        assert_eq!("gbff".parse::<Format>(), Ok(Format::Genbank));
+        assert_eq!("GP".parse::<Format>(), Ok(Format::Genpept));
+        assert!("sam".parse::<Format>().is_err());
+        assert!(Format::Embl.has_reader());
+        assert!(!Format::Fasta.has_reader());
+        let error = records(&b">T_1\nATG\n"[..], Format::Fasta).err().unwrap();
+        assert_eq!(error.to_string(), "fasta input has no feature table reader");
+    }
+
+    #[test]
+    fn reads_records_in_the_format_detected() {
+        let text = "LOCUS       T1  6 bp    DNA     linear   BCT 01-JAN-2020\n\
+                    ORIGIN\n\
+                    \x20       1 atgtaa\n\
+                    //\n";
+        let mut reader = text.as_bytes();
+        let format = detect(&mut reader).unwrap().unwrap();
+        assert_eq!(format, Format::Genbank);
+        let ids: Vec<String> = records(reader, format)
+            .unwrap()
+            .map(|record| record.unwrap().id().to_string())
+            .collect();
+        assert_eq!(ids, vec!["T1"]);
+    }
+}
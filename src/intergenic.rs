@@ -0,0 +1,317 @@
+use crate::location::{Location, Segment};
+use crate::record::Feature;
+use std::fmt;
+use std::str::FromStr;
+
+/// Relative orientation of the two features around a gap, in genomic order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    /// `-> <-`, sharing a terminator region.
+    Convergent,
+    /// `<- ->`, sharing a promoter region.
+    Divergent,
+    /// Both features on the same strand.
+    Tandem,
+}
+
+impl Orientation {
+    fn of(left: &Feature, right: &Feature) -> Self {
+        match (left.location.strand(), right.location.strand()) {
+            (1, -1) => Orientation::Convergent,
+            (-1, 1) => Orientation::Divergent,
+            _ => Orientation::Tandem,
+        }
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Orientation::Convergent => write!(f, "convergent"),
+            Orientation::Divergent => write!(f, "divergent"),
+            Orientation::Tandem => write!(f, "tandem"),
+        }
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "convergent" => Ok(Orientation::Convergent),
+            "divergent" => Ok(Orientation::Divergent),
+            "tandem" => Ok(Orientation::Tandem),
+            _ => Err(format!(
+                "unknown orientation {:?}, expected convergent, divergent or tandem",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct IntergenicOptions {
+    /// Only consider neighbours on the same strand, and write each gap on
+    /// that strand.
+    pub strand_aware: bool,
+    /// Keep only gaps between pairs in this orientation.
+    pub orientation: Option<Orientation>,
+    pub min_length: usize,
+}
+
+/// The gap between two neighbouring features.
+#[derive(Clone, Debug)]
+pub struct Intergenic {
+    /// Position of the feature before the gap among those given to
+    /// `intergenic_regions`.
+    pub left: usize,
+    /// Position of the feature after the gap.
+    pub right: usize,
+    pub location: Location,
+    pub orientation: Orientation,
+}
+
+/// The stretches of the record a feature covers, in genomic order. A
+/// feature such as `join(2900..3000,1..50)` that runs across the origin is
+/// split into its `2900..3000` and `1..50` pieces, so it can bound the gap
+/// across the origin as well as those around it.
+fn spans(feature: &Feature, length: usize) -> Vec<(usize, usize)> {
+    let mut segments: Vec<&Segment> = feature.location.segments.iter().collect();
+    if feature.location.strand() == -1 {
+        segments.reverse();
+    }
+    let wrap = segments
+        .windows(2)
+        .position(|pair| pair[1].start < pair[0].start);
+    match wrap {
+        Some(i) => {
+            let before = segments[..=i].iter().map(|s| s.start).min().unwrap_or(0);
+            let after = segments[i + 1..].iter().map(|s| s.end).max().unwrap_or(0);
+            vec![(0, after), (before, length.max(before))]
+        }
+        None => vec![(feature.location.start(), feature.location.end())],
+    }
+}
+
+/// Gaps between consecutive `features` of a record of `length` bp, including
+/// the gap across the origin of circular records.
+pub fn intergenic_regions(
+    features: &[&Feature],
+    length: usize,
+    circular: bool,
+    options: &IntergenicOptions,
+) -> Vec<Intergenic> {
+    let strands: &[Option<i32>] = if options.strand_aware {
+        &[Some(1), Some(-1)]
+    } else {
+        &[None]
+    };
+    let mut regions = Vec::new();
+    for strand in strands {
+        let mut sorted: Vec<Span> = features
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, f)| !f.location.is_empty())
+            .filter(|(_, f)| strand.is_none_or(|s| f.location.strand() == s))
+            .flat_map(|(i, f)| {
+                spans(f, length).into_iter().map(move |(start, end)| Span {
+                    index: i,
+                    feature: f,
+                    start,
+                    end,
+                })
+            })
+            .collect();
+        sorted.sort_by_key(|span| (span.start, span.end));
+        let orient = strand.unwrap_or(1);
+        let mut left = match sorted.first() {
+            Some(first) => *first,
+            None => continue,
+        };
+        for &right in sorted.iter().skip(1) {
+            if left.end < right.start {
+                regions.push(gap(left, right, vec![(left.end, right.start)], orient));
+            }
+            if right.end > left.end {
+                left = right;
+            }
+        }
+        let first = sorted[0];
+        if circular && left.end < length + first.start {
+            let mut pieces = Vec::new();
+            if left.end < length {
+                pieces.push((left.end, length));
+            }
+            if first.start > 0 {
+                pieces.push((0, first.start));
+            }
+            if !pieces.is_empty() {
+                regions.push(gap(left, first, pieces, orient));
+            }
+        }
+    }
+    regions.retain(|region| {
+        region.location.len() >= options.min_length.max(1)
+            && options.orientation.is_none_or(|o| o == region.orientation)
+    });
+    regions
+}
+
+/// One stretch of a feature, with the feature's position among those given
+/// to `intergenic_regions`.
+#[derive(Clone, Copy)]
+struct Span<'a> {
+    index: usize,
+    feature: &'a Feature,
+    start: usize,
+    end: usize,
+}
+
+fn gap(left: Span, right: Span, pieces: Vec<(usize, usize)>, strand: i32) -> Intergenic {
+    let mut segments: Vec<Segment> = pieces
+        .into_iter()
+        .map(|(start, end)| Segment {
+            start,
+            end,
+            strand,
+            ..Default::default()
+        })
+        .collect();
+    if strand == -1 {
+        segments.reverse();
+    }
+    Intergenic {
+        left: left.index,
+        right: right.index,
+        location: Location {
+            segments,
+            incomplete: false,
+        },
+        orientation: Orientation::of(left.feature, right.feature),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gene(location: &str) -> Feature {
+        Feature {
+            kind: "gene".to_string(),
+            location: Location::parse(location).unwrap(),
+            qualifiers: Vec::new(),
+        }
+    }
+
+    /// `(left, right, location, orientation)` of every gap.
+    fn gaps(
+        locations: &[&str],
+        circular: bool,
+        options: IntergenicOptions,
+    ) -> Vec<(usize, usize, String, String)> {
+        let features: Vec<Feature> = locations.iter().map(|l| gene(l)).collect();
+        let features: Vec<&Feature> = features.iter().collect();
+        intergenic_regions(&features, 3000, circular, &options)
+            .into_iter()
+            .map(|r| {
+                (
+                    r.left,
+                    r.right,
+                    r.location.to_string(),
+                    r.orientation.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn gap(
+        left: usize,
+        right: usize,
+        location: &str,
+        orientation: &str,
+    ) -> (usize, usize, String, String) {
+        (left, right, location.to_string(), orientation.to_string())
+    }
+
+    const GENES: [&str; 4] = [
+        "101..200",
+        "complement(301..400)",
+        "complement(351..380)",
+        "501..600",
+    ];
+
+    #[test]
+    fn finds_gaps_between_neighbours() {
+        assert_eq!(
+            gaps(&GENES, false, IntergenicOptions::default()),
+            vec![
+                gap(0, 1, "201..300", "convergent"),
+                gap(1, 3, "401..500", "divergent"),
+            ]
+        );
+        assert_eq!(
+            gaps(&GENES, true, IntergenicOptions::default()),
+            vec![
+                gap(0, 1, "201..300", "convergent"),
+                gap(1, 3, "401..500", "divergent"),
+                gap(3, 0, "join(601..3000,1..100)", "tandem"),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_by_strand_orientation_and_length() {
+        let strand_aware = IntergenicOptions {
+            strand_aware: true,
+            ..IntergenicOptions::default()
+        };
+        assert_eq!(
+            gaps(&GENES, false, strand_aware),
+            vec![gap(0, 3, "201..500", "tandem")]
+        );
+        let divergent = IntergenicOptions {
+            orientation: Some(Orientation::Divergent),
+            ..IntergenicOptions::default()
+        };
+        assert_eq!(
+            gaps(&GENES, false, divergent),
+            vec![gap(1, 3, "401..500", "divergent")]
+        );
+        let long = IntergenicOptions {
+            min_length: 101,
+            ..IntergenicOptions::default()
+        };
+        assert!(gaps(&GENES, false, long).is_empty());
+    }
+
+    #[test]
+    fn features_across_the_origin_bound_the_gaps_beside_them() {
+        let genes = ["101..200", "2001..2099", "join(2951..3000,1..49)"];
+        assert_eq!(
+            gaps(&genes, true, IntergenicOptions::default()),
+            vec![
+                gap(2, 0, "50..100", "tandem"),
+                gap(0, 1, "201..2000", "tandem"),
+                gap(1, 2, "2100..2950", "tandem"),
+            ]
+        );
+        let genes = ["101..200", "complement(join(2951..3000,1..49))"];
+        assert_eq!(
+            gaps(&genes, true, IntergenicOptions::default()),
+            vec![
+                gap(1, 0, "50..100", "divergent"),
+                gap(0, 1, "201..2950", "convergent"),
+            ]
+        );
+        assert_eq!(
+            gaps(
+                &["join(2951..3000,1..49)"],
+                true,
+                IntergenicOptions::default()
+            ),
+            vec![gap(0, 0, "50..2950", "tandem")]
+        );
+    }
+}
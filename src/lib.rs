@@ -0,0 +1,29 @@
+//! Shared readers and conversions for GenBank and EMBL flat files.
+
+pub mod bgzf;
+pub mod compression;
+pub mod convert;
+pub mod embl;
+pub mod fasta;
+pub mod filter;
+pub mod flank;
+pub mod gbk;
+pub mod header;
+pub mod ids;
+pub mod index;
+pub mod input;
+pub mod intergenic;
+pub mod lines;
+pub mod location;
+pub mod mmap;
+pub mod ncbi;
+pub mod order;
+pub mod parallel;
+pub mod policy;
+pub mod record;
+pub mod region;
+pub mod select;
+pub mod sink;
+pub mod stats;
+pub mod translate;
+pub mod verify;
@@ -0,0 +1,188 @@
+use memchr::memchr;
+use std::io::{self, BufRead};
+use std::str;
+
+/// Reads lines as byte slices borrowed from the buffer of `B` when they fit
+/// in it, copying only lines that straddle a refill.
+#[derive(Debug)]
+pub struct LineReader<B> {
+    reader: B,
+    /// Bytes of the current line still to be consumed from `reader`.
+    pending: usize,
+    /// The current line when it had to be copied.
+    line: Vec<u8>,
+}
+
+impl<B: BufRead> LineReader<B> {
+    pub fn new(reader: B) -> Self {
+        LineReader {
+            reader,
+            pending: 0,
+            line: Vec::new(),
+        }
+    }
+
+    /// The next line including its `\n`, or `None` at the end of the input.
+    pub fn next_line(&mut self) -> io::Result<Option<&[u8]>> {
+        self.reader.consume(self.pending);
+        self.pending = 0;
+        self.line.clear();
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(if self.line.is_empty() {
+                    None
+                } else {
+                    Some(&self.line)
+                });
+            }
+            match memchr(b'\n', buffer) {
+                Some(end) if self.line.is_empty() => {
+                    self.pending = end + 1;
+                    return Ok(Some(&self.reader.fill_buf()?[..end + 1]));
+                }
+                Some(end) => {
+                    self.line.extend_from_slice(&buffer[..=end]);
+                    self.reader.consume(end + 1);
+                    return Ok(Some(&self.line));
+                }
+                None => {
+                    let length = buffer.len();
+                    self.line.extend_from_slice(buffer);
+                    self.reader.consume(length);
+                }
+            }
+        }
+    }
+}
+
+/// `line` as text, failing like `read_line` does on invalid UTF-8.
+pub fn text(line: &[u8]) -> io::Result<&str> {
+    str::from_utf8(line).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })
+}
+
+/// Append the bytes of `line` that are not whitespace to `sequence`.
+pub fn push_bases(sequence: &mut Vec<u8>, line: &[u8]) {
+    sequence.extend(line.iter().filter(|b| !b.is_ascii_whitespace()));
+}
+
+/// `line` without its first word, e.g. the position that starts a GenBank
+/// `ORIGIN` line.
+pub fn after_first_word(line: &[u8]) -> &[u8] {
+    let start = line
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(line.len());
+    let line = &line[start..];
+    let end = line
+        .iter()
+        .position(u8::is_ascii_whitespace)
+        .unwrap_or(line.len());
+    &line[end..]
+}
+
+/// `line` without its last word, e.g. the count that ends an EMBL sequence
+/// line.
+pub fn before_last_word(line: &[u8]) -> &[u8] {
+    let end = line
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(0, |end| end + 1);
+    let line = &line[..end];
+    let start = line.iter().rposition(u8::is_ascii_whitespace).unwrap_or(0);
+    &line[..start]
+}
+
+/// Split `text` into lines of at most `width` bytes, breaking after the
+/// last `separator` that fits, or anywhere when none does. A space
+/// separator is dropped, as readers put it back when joining the lines.
+pub fn wrap(mut text: &str, width: usize, separator: Option<u8>) -> Vec<&str> {
+    let mut lines = Vec::new();
+    while text.len() > width {
+        let mut cut = width;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let found = separator.and_then(|separator| {
+            text.as_bytes()[..cut]
+                .iter()
+                .rposition(|&b| b == separator)
+                .filter(|&i| i > 0)
+                .map(|i| (i, separator))
+        });
+        match found {
+            Some((i, b' ')) => {
+                lines.push(&text[..i]);
+                text = &text[i + 1..];
+            }
+            Some((i, _)) => {
+                lines.push(&text[..=i]);
+                text = &text[i + 1..];
+            }
+            None => {
+                lines.push(&text[..cut]);
+                text = &text[cut..];
+            }
+        }
+    }
+    lines.push(text);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lines_across_buffer_refills() {
+        let text = b"ab\ncdefghij\n\nlast";
+        for capacity in [1, 2, 4, 64] {
+            let mut reader = LineReader::new(io::BufReader::with_capacity(capacity, &text[..]));
+            let mut lines = Vec::new();
+            while let Some(line) = reader.next_line().unwrap() {
+                lines.push(line.to_vec());
+            }
+            assert_eq!(
+                lines,
+                vec![
+                    b"ab\n".to_vec(),
+                    b"cdefghij\n".to_vec(),
+                    b"\n".to_vec(),
+                    b"last".to_vec()
+                ],
+                "capacity {}",
+                capacity
+            );
+        }
+    }
+
+    #[test]
+    fn strips_sequence_line_numbers() {
+        let mut sequence = Vec::new();
+        push_bases(
+            &mut sequence,
+            after_first_word(b"       61 acgtacgtac gtac\n"),
+        );
+        push_bases(
+            &mut sequence,
+            before_last_word(b"     acgtacgtac gtac                    20\n"),
+        );
+        assert_eq!(sequence, b"acgtacgtacgtacacgtacgtacgtac");
+        assert_eq!(after_first_word(b"  61\n"), b"\n");
+        assert_eq!(before_last_word(b"20"), b"");
+    }
+
+    #[test]
+    fn rejects_invalid_utf8() {
+        assert_eq!(text(b"caf\xc3\xa9").unwrap(), "café");
+        assert_eq!(
+            text(b"caf\xe9").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use bio::alphabets::dna::revcomp;
+use std::fmt;
+use std::io;
+
+/// One contiguous span of a feature location, 0-based and end-exclusive.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub start: usize,
+    pub end: usize,
+    pub strand: i32,
+    /// `<` before the lower coordinate.
+    pub partial_start: bool,
+    /// `>` before the upper coordinate.
+    pub partial_end: bool,
+}
+
+/// A parsed INSDC feature location with segments in biological (5' to 3') order.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Location {
+    pub segments: Vec<Segment>,
+    /// Part of the location lies in another record (`ACC.1:1..100`) or
+    /// between two bases (`12^13`), so the segments miss some of the feature.
+    pub incomplete: bool,
+}
+
+impl Location {
+    /// Parse a location string such as `complement(join(<1..200,300..>450))`.
+    pub fn parse(text: &str) -> io::Result<Location> {
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut parser = Parser {
+            text: compact.as_bytes(),
+            pos: 0,
+            incomplete: false,
+        };
+        let segments = parser.location()?;
+        if parser.pos != parser.text.len() {
+            return Err(invalid(text));
+        }
+        Ok(Location {
+            segments,
+            incomplete: parser.incomplete,
+        })
+    }
+    /// Strand of the first segment, 1 or -1.
+    pub fn strand(&self) -> i32 {
+        self.segments.first().map(|s| s.strand).unwrap_or(1)
+    }
+    /// Lowest 0-based coordinate covered.
+    pub fn start(&self) -> usize {
+        self.segments.iter().map(|s| s.start).min().unwrap_or(0)
+    }
+    /// Highest end-exclusive coordinate covered.
+    pub fn end(&self) -> usize {
+        self.segments.iter().map(|s| s.end).max().unwrap_or(0)
+    }
+    /// Start of the first segment as written, so `2899` for
+    /// `join(2900..3000,1..50)` where `start` gives 0.
+    pub fn written_start(&self) -> usize {
+        let first = if self.strand() == -1 {
+            self.segments.last()
+        } else {
+            self.segments.first()
+        };
+        first.map_or(0, |s| s.start)
+    }
+    /// End of the last segment as written, so `50` for
+    /// `join(2900..3000,1..50)` where `end` gives 3000.
+    pub fn written_end(&self) -> usize {
+        let last = if self.strand() == -1 {
+            self.segments.first()
+        } else {
+            self.segments.last()
+        };
+        last.map_or(0, |s| s.end)
+    }
+    /// Total number of bases covered by all segments.
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|s| s.end - s.start).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// True when the 5' end of the feature lies outside the known sequence.
+    pub fn five_prime_partial(&self) -> bool {
+        match self.segments.first() {
+            Some(s) if s.strand == -1 => s.partial_end,
+            Some(s) => s.partial_start,
+            None => false,
+        }
+    }
+    /// True when the 3' end of the feature lies outside the known sequence.
+    pub fn three_prime_partial(&self) -> bool {
+        match self.segments.last() {
+            Some(s) if s.strand == -1 => s.partial_start,
+            Some(s) => s.partial_end,
+            None => false,
+        }
+    }
+    /// Where the 0-based `position` falls in the spliced sequence, or
+    /// `None` when no segment covers it.
+    pub fn spliced_index(&self, position: usize) -> Option<usize> {
+        let mut before = 0;
+        for segment in &self.segments {
+            if (segment.start..segment.end).contains(&position) {
+                return Some(
+                    before
+                        + if segment.strand == -1 {
+                            segment.end - 1 - position
+                        } else {
+                            position - segment.start
+                        },
+                );
+            }
+            before += segment.end - segment.start;
+        }
+        None
+    }
+    /// The part of the location inside `start..end`, shifted so `start`
+    /// becomes 0, with the ends that were cut off marked partial. Also
+    /// returns how many bases were cut from the 5' end. `None` when no base
+    /// lies inside.
+    pub fn clip(&self, start: usize, end: usize) -> Option<(Location, usize)> {
+        let mut segments: Vec<Segment> = Vec::new();
+        let (mut five_prime, mut three_prime) = (0, 0);
+        for segment in &self.segments {
+            let (lower, upper) = (segment.start.max(start), segment.end.min(end));
+            if lower >= upper {
+                if segments.is_empty() {
+                    five_prime += segment.end - segment.start;
+                } else {
+                    three_prime += segment.end - segment.start;
+                }
+                continue;
+            }
+            let (before, after) = (lower - segment.start, segment.end - upper);
+            if segments.is_empty() {
+                five_prime += if segment.strand == -1 { after } else { before };
+            }
+            three_prime = if segment.strand == -1 { before } else { after };
+            segments.push(Segment {
+                start: lower - start,
+                end: upper - start,
+                strand: segment.strand,
+                partial_start: segment.partial_start || before > 0,
+                partial_end: segment.partial_end || after > 0,
+            });
+        }
+        let first = segments.first_mut()?;
+        if five_prime > 0 {
+            match first.strand {
+                -1 => first.partial_end = true,
+                _ => first.partial_start = true,
+            }
+        }
+        let last = segments.last_mut()?;
+        if three_prime > 0 {
+            match last.strand {
+                -1 => last.partial_start = true,
+                _ => last.partial_end = true,
+            }
+        }
+        Some((
+            Location {
+                segments,
+                incomplete: self.incomplete,
+            },
+            five_prime,
+        ))
+    }
+    /// Splice the segments out of `sequence`, reverse complementing each
+    /// segment on the minus strand.
+    pub fn extract(&self, sequence: &[u8]) -> io::Result<Vec<u8>> {
+        let mut spliced = Vec::with_capacity(self.len());
+        for segment in &self.segments {
+            if segment.end > sequence.len() || segment.start > segment.end {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "location {}..{} lies outside a sequence of {} bp",
+                        segment.start + 1,
+                        segment.end,
+                        sequence.len()
+                    ),
+                ));
+            }
+            let slice = &sequence[segment.start..segment.end];
+            if segment.strand == -1 {
+                spliced.extend_from_slice(&revcomp(slice));
+            } else {
+                spliced.extend_from_slice(slice);
+            }
+        }
+        Ok(spliced)
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lower = if self.partial_start { "<" } else { "" };
+        let upper = if self.partial_end { ">" } else { "" };
+        if self.end == self.start + 1 && !self.partial_start && !self.partial_end {
+            write!(f, "{}", self.end)
+        } else {
+            write!(f, "{}{}..{}{}", lower, self.start + 1, upper, self.end)
+        }
+    }
+}
+
+/// Writes the location back in INSDC syntax, 1-based.
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let all_minus = self.segments.iter().all(|s| s.strand == -1);
+        let spans: Vec<String> = if all_minus {
+            self.segments.iter().rev().map(|s| s.to_string()).collect()
+        } else {
+            self.segments
+                .iter()
+                .map(|s| {
+                    if s.strand == -1 {
+                        format!("complement({})", s)
+                    } else {
+                        s.to_string()
+                    }
+                })
+                .collect()
+        };
+        let joined = if spans.len() == 1 {
+            spans[0].clone()
+        } else {
+            format!("join({})", spans.join(","))
+        };
+        if all_minus && !self.segments.is_empty() {
+            write!(f, "complement({})", joined)
+        } else {
+            write!(f, "{}", joined)
+        }
+    }
+}
+
+fn invalid(text: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Could not parse feature location {:?}", text),
+    )
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    incomplete: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn location(&mut self) -> io::Result<Vec<Segment>> {
+        if self.eat("complement(") {
+            let mut inner = self.location()?;
+            self.expect(b')')?;
+            inner.reverse();
+            for segment in inner.iter_mut() {
+                segment.strand = -segment.strand;
+            }
+            Ok(inner)
+        } else if self.eat("join(") || self.eat("order(") || self.eat("bond(") {
+            let mut segments = self.location()?;
+            while self.eat(",") {
+                segments.extend(self.location()?);
+            }
+            self.expect(b')')?;
+            Ok(segments)
+        } else {
+            Ok(self.span()?.into_iter().collect())
+        }
+    }
+    /// A single `a..b`, `a.b`, `a^b` or `a` span; remote references and
+    /// sites between bases yield nothing and mark the location incomplete.
+    fn span(&mut self) -> io::Result<Option<Segment>> {
+        let begin = self.pos;
+        while self.pos < self.text.len() && !b",()".contains(&self.text[self.pos]) {
+            self.pos += 1;
+        }
+        let span = std::str::from_utf8(&self.text[begin..self.pos]).unwrap_or_default();
+        if span.contains(':') {
+            self.incomplete = true;
+            return Ok(None);
+        }
+        let err = || invalid(span);
+        let (first, last, between) = if let Some((a, b)) = span.split_once("..") {
+            (a, b, false)
+        } else if let Some((a, b)) = span.split_once('^') {
+            (a, b, true)
+        } else if let Some((a, b)) = span.split_once('.') {
+            (a, b, false)
+        } else {
+            (span, span, false)
+        };
+        let partial_start = first.starts_with('<');
+        let partial_end = last.starts_with('>');
+        let start = first
+            .trim_start_matches(&['<', '>'][..])
+            .parse::<usize>()
+            .map_err(|_| err())?;
+        let end = last
+            .trim_start_matches(&['<', '>'][..])
+            .parse::<usize>()
+            .map_err(|_| err())?;
+        if between {
+            self.incomplete = true;
+            return Ok(None);
+        }
+        if start == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Segment {
+            start: start - 1,
+            end,
+            strand: 1,
+            partial_start,
+            partial_end,
+        }))
+    }
+    fn eat(&mut self, token: &str) -> bool {
+        if self.text[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, byte: u8) -> io::Result<()> {
+        if self.text.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(invalid(&String::from_utf8_lossy(self.text)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_back_what_it_parses() {
+        for text in [
+            "5",
+            "1..100",
+            "<1..>100",
+            "complement(1..100)",
+            "join(1..10,20..30)",
+            "complement(join(<1..10,20..>30))",
+            "join(1..10,complement(20..30))",
+            "join(2900..3000,1..50)",
+        ] {
+            let location = Location::parse(text).unwrap();
+            assert_eq!(location.to_string(), text);
+            assert_eq!(Location::parse(&location.to_string()).unwrap(), location);
+        }
+    }
+
+    #[test]
+    fn normalises_equivalent_forms() {
+        for (text, written) in [
+            (
+                "join(complement(20..30),complement(1..10))",
+                "complement(join(1..10,20..30))",
+            ),
+            ("order(1..10, 20..30)", "join(1..10,20..30)"),
+            ("3.5", "3..5"),
+        ] {
+            assert_eq!(Location::parse(text).unwrap().to_string(), written);
+        }
+    }
+
+    #[test]
+    fn marks_remote_parts_and_sites_incomplete() {
+        let remote = Location::parse("join(1..10,AB000001.1:1..5)").unwrap();
+        assert!(remote.incomplete);
+        assert_eq!(remote.len(), 10);
+        assert!(Location::parse("12^13").unwrap().incomplete);
+        assert!(!Location::parse("join(1..10,20..30)").unwrap().incomplete);
+    }
+}
@@ -1,219 +1,1040 @@
-use std::io;
-use std::fs;
-use std::env;
-use itertools::Itertools;
-use std::vec::Vec;
-use std::convert::AsRef;
-use std::path::Path;
-use std::process;
+use annots::compression::{self, Compression};
+use annots::convert::{Converter, Options, Output};
+use annots::fasta::{Case, Writer, NUCLEOTIDE_WIDTH, PROTEIN_WIDTH};
+use annots::filter::Filter;
+use annots::flank::Flanks;
+use annots::header::Template;
+use annots::ids::IdChain;
+use annots::index::{self, IndexedReader, Source};
+use annots::input::{self, Format};
+use annots::intergenic::{IntergenicOptions, Orientation};
+use annots::mmap::MappedFile;
+use annots::order::SortOrder;
+use annots::parallel;
+use annots::policy::{Policies, Policy};
+use annots::record::Record;
+use annots::region::Region;
+use annots::select::{AccessionList, Selection, Topology};
+use annots::sink::Sink;
+use annots::stats::{self, RecordStats};
+use annots::translate::StopPolicy;
+use annots::verify;
+use annots::{embl, gbk};
 use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use regex::Regex;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Convert, extract from and summarise GenBank and EMBL flat files.
+#[derive(Parser)]
+#[command(name = "annots", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
+#[derive(Subcommand)]
+enum Command {
+    /// Write whole records (fna), CDS proteins (faa) or CDS nucleotides (ffn) as fasta
+    Convert(ConvertArgs),
+    /// Write the sequences of selected features, their flanks or the gaps between them
+    Extract(ExtractArgs),
+    /// Compare CDS translations with their /translation qualifiers, as TSV
+    Verify(VerifyArgs),
+    /// Summarise each record as a row of TSV
+    Stats(StatsArgs),
+    /// Write an index of record offsets next to each plain or bgzipped file, as <file>.idx
+    Index(IndexArgs),
+    /// Write single records, or ranges of their sequence as fasta, from an indexed file
+    Fetch(FetchArgs),
+}
 
-//const MAX_GBK_BUFFER_SIZE: usize = 512;
-/// A Gbk reader.
+#[derive(Args)]
+struct InputArgs {
+    /// GenBank or EMBL files, directories or quoted glob patterns to read
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Format of the input [default: detected from its first bytes]
+    #[arg(long, value_name = "genbank|embl|genpept")]
+    from: Option<Format>,
+    /// Threads parsing and converting records, 0 for one per core; output
+    /// keeps the input order
+    #[arg(short = 'j', long, value_name = "N", default_value_t = 1)]
+    threads: usize,
+    /// Map uncompressed inputs into memory instead of streaming them;
+    /// compressed inputs are still streamed
+    #[arg(long)]
+    mmap: bool,
+    /// Only records named in this file, one id, accession or
+    /// accession.version per line
+    #[arg(long, value_name = "FILE")]
+    include: Option<PathBuf>,
+    /// Skip records named in this file
+    #[arg(long, value_name = "FILE")]
+    exclude: Option<PathBuf>,
+    /// Skip records shorter than this
+    #[arg(long, value_name = "BASES")]
+    min_record_length: Option<u32>,
+    /// Skip records longer than this
+    #[arg(long, value_name = "BASES")]
+    max_record_length: Option<u32>,
+    /// Only records whose DEFINITION matches this regex
+    #[arg(long, value_name = "REGEX")]
+    definition: Option<Regex>,
+    /// Only records with this taxon in their lineage, e.g. Enterobacterales
+    #[arg(long, value_name = "NAME")]
+    taxon: Option<String>,
+    /// Only linear or only circular records
+    #[arg(long, value_name = "linear|circular")]
+    topology: Option<Topology>,
+    /// Only records of this molecule type, e.g. DNA or mRNA
+    #[arg(long, value_name = "TYPE")]
+    molecule: Option<String>,
+}
 
+#[derive(Args)]
+struct OutputArgs {
+    /// Write to this file instead of stdout; it only appears once complete
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Compress the output [default: from the extension of -o, otherwise none]
+    #[arg(long, value_name = "none|gzip|bgzf|bzip2|xz|zstd")]
+    compress: Option<Compression>,
+}
 
-pub trait GbkRead {
-    fn read(&mut self, record: &mut Record) -> io::Result<()>;
+#[derive(Args)]
+struct BatchArgs {
+    /// Write each record to its own file in this directory instead
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+    /// Name of each file under --output-dir, e.g. '{assembly}.faa' for one
+    /// file per input [default: {accession}.<fna|faa|ffn>]
+    #[arg(long, value_name = "TEMPLATE", requires = "output_dir")]
+    file_name: Option<Template>,
+    /// Put this before every id, e.g. '{assembly}|'; {file} and {assembly}
+    /// come from the input file name
+    #[arg(long, value_name = "TEMPLATE")]
+    id_prefix: Option<Template>,
+    /// Write a TSV of input, format, output, records and sequences
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
 }
 
-#[derive(Debug)]
-pub struct Reader<B> {
-    reader: B,
-    line_buffer: String,
+#[derive(Args)]
+struct PolicyArgs {
+    /// /pseudo and /pseudogene CDS [default: include, or skip for --ncbi faa]
+    #[arg(long, value_name = "skip|include|flag")]
+    pseudo: Option<Policy>,
+    /// CDS with < or > partial ends
+    #[arg(long, value_name = "skip|include|flag", default_value = "include")]
+    partial: Policy,
+    /// CDS with /ribosomal_slippage
+    #[arg(long, value_name = "skip|include|flag", default_value = "include")]
+    slippage: Policy,
 }
 
-impl Reader<io::BufReader<fs::File>> {
-    /// Read Gbk from given file path in given format.
-    pub fn from_file<P: AsRef<Path> + std::fmt::Debug>(path: P) -> anyhow::Result<Self> {
-        fs::File::open(&path)
-            .map(Reader::new)
-            .with_context(|| format!("Failed to read Gbk from {:#?}", path))
+impl PolicyArgs {
+    fn policies(&self) -> Policies {
+        Policies {
+            pseudo: self.pseudo.unwrap_or(Policy::Include),
+            partial: self.partial,
+            slippage: self.slippage,
+        }
     }
 }
 
-impl<R> Reader<io::BufReader<R>>
-where
-     R: io::Read,
-{
-    //// Create a new Gbk reader given an instance of `io::Read` in given format
-    pub fn new(reader: R) -> Self {
-        Reader {
-            reader: io::BufReader::new(reader),
-	    line_buffer: String::new(),
+#[derive(Args)]
+struct FastaArgs {
+    /// Header template, e.g. '{locus_tag}|{gene:-}|{product}'
+    #[arg(long, value_name = "TEMPLATE")]
+    header: Option<Template>,
+    /// NCBI Datasets style headers, as in cds_from_genomic.fna and translated_cds.faa
+    #[arg(long)]
+    ncbi: bool,
+    /// Qualifiers tried in order to name each feature
+    #[arg(
+        long,
+        value_name = "QUALIFIERS",
+        default_value = "locus_tag,protein_id,gene"
+    )]
+    id_chain: IdChain,
+    /// Order of the features within each record
+    #[arg(
+        long,
+        value_name = "file|coordinate|id|natural",
+        default_value = "file"
+    )]
+    sort: SortOrder,
+    /// Letters per line, 0 for unwrapped [default: 80, or 60 for proteins without --ncbi]
+    #[arg(long, value_name = "LETTERS")]
+    width: Option<usize>,
+    /// Case of the sequence letters
+    #[arg(long, value_name = "preserve|upper|lower", default_value = "preserve")]
+    case: Case,
+    /// Only features matching this, e.g.
+    /// 'product ~ /transposase/i && length >= 300 && !pseudo'
+    #[arg(long = "where", value_name = "EXPR")]
+    filter: Option<Filter>,
+    #[command(flatten)]
+    policies: PolicyArgs,
+}
+
+impl FastaArgs {
+    fn options(&self) -> Options {
+        Options {
+            header: self.header.clone(),
+            ncbi: self.ncbi,
+            id_chain: self.id_chain.clone(),
+            sort: self.sort,
+            policies: self.policies.policies(),
+            filter: self.filter.clone(),
+            ..Options::default()
         }
     }
 }
 
-impl<B> Reader<B>
-where
-    B: io::BufRead,
-{
-    pub fn from_bufread(bufreader: B) -> Self {
-        Reader {
-	     reader: bufreader,
-	     line_buffer: String::new(),
-	}
+#[derive(Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Kind of fasta to write
+    #[arg(long, value_name = "fna|faa|ffn", default_value = "fna")]
+    to: Output,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    batch: BatchArgs,
+    #[command(flatten)]
+    fasta: FastaArgs,
+    /// Write /translation when present, translating only CDS without one (faa)
+    #[arg(long)]
+    annotated: bool,
+    /// Record whether each protein was annotated or translated (faa)
+    #[arg(long, value_name = "FILE")]
+    source_report: Option<PathBuf>,
+    /// Stop codons in translated proteins (faa) [default: truncate, or strip with --ncbi]
+    #[arg(long, value_name = "keep|strip|truncate|mask")]
+    stops: Option<StopPolicy>,
+}
+
+#[derive(Args)]
+struct ExtractArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    batch: BatchArgs,
+    #[command(flatten)]
+    fasta: FastaArgs,
+    /// Feature keys to extract [default: CDS, or any key with --where]
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    types: Option<Vec<String>>,
+    /// Bases added before the 5' end of each feature
+    #[arg(long, value_name = "BASES", default_value_t = 0)]
+    upstream: usize,
+    /// Bases added after the 3' end of each feature
+    #[arg(long, value_name = "BASES", default_value_t = 0)]
+    downstream: usize,
+    /// Write only the flanks, as <id>_upstream and <id>_downstream
+    #[arg(long)]
+    flanks_only: bool,
+    /// Write the gaps between consecutive features instead
+    #[arg(long, conflicts_with_all = ["upstream", "downstream", "flanks_only"])]
+    intergenic: bool,
+    /// Only pair neighbours on the same strand (--intergenic)
+    #[arg(long, requires = "intergenic")]
+    strand_aware: bool,
+    /// Keep one orientation of neighbours (--intergenic)
+    #[arg(
+        long,
+        value_name = "convergent|divergent|tandem",
+        requires = "intergenic"
+    )]
+    pairs: Option<Orientation>,
+    /// Drop gaps shorter than this (--intergenic)
+    #[arg(
+        long,
+        value_name = "BASES",
+        default_value_t = 0,
+        requires = "intergenic"
+    )]
+    min_length: usize,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    policies: PolicyArgs,
+    /// Qualifiers tried in order to name each CDS
+    #[arg(
+        long,
+        value_name = "QUALIFIERS",
+        default_value = "locus_tag,protein_id,gene"
+    )]
+    id_chain: IdChain,
+}
+
+#[derive(Args)]
+struct StatsArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args)]
+struct IndexArgs {
+    /// GenBank or EMBL files to index, uncompressed or compressed with bgzip
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct FetchArgs {
+    /// Indexed GenBank or EMBL file
+    file: PathBuf,
+    /// Records by id, accession or version, each optionally with a 1-based
+    /// range as NAME:START-END
+    #[arg(required = true, value_name = "REGION")]
+    regions: Vec<Region>,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Letters per line of ranges, 0 for unwrapped
+    #[arg(long, value_name = "LETTERS", default_value_t = NUCLEOTIDE_WIDTH)]
+    width: usize,
+    /// Write each record, or each range cut out with the features
+    /// overlapping it, as a flat file record in this format
+    #[arg(long, value_name = "genbank|embl", conflicts_with = "width")]
+    to: Option<Format>,
+}
+
+impl InputArgs {
+    /// Input files, with directories and glob patterns expanded.
+    fn files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        input::expand(&self.inputs)
     }
-    pub fn records(self) -> Records<B> {
-        Records {
-	   reader: self,
-	   error_has_occurred: false,
-	}
+
+    fn format(&self, path: &Path) -> anyhow::Result<Format> {
+        match self.from {
+            Some(format) => Ok(format),
+            None => input::sniff(path)?
+                .with_context(|| format!("Could not recognise the format of {:?}", path)),
+        }
+    }
+
+    /// The records to read, with the accession lists loaded.
+    fn selection(&self) -> anyhow::Result<Selection> {
+        Ok(Selection {
+            include: self
+                .include
+                .as_ref()
+                .map(AccessionList::from_file)
+                .transpose()?,
+            exclude: self
+                .exclude
+                .as_ref()
+                .map(AccessionList::from_file)
+                .transpose()?,
+            min_length: self.min_record_length,
+            max_length: self.max_record_length,
+            definition: self.definition.clone(),
+            taxon: self.taxon.clone(),
+            topology: self.topology,
+            molecule: self.molecule.clone(),
+        })
     }
-}
 
-impl<B> GbkRead for Reader<B>
-where
-    B: io::BufRead,
-{
-    fn read(& mut self, record: &mut Record) -> io::Result<()> {
-        record.rec_clear();
-	let mut sequences = String::new();
-    	if self.line_buffer.is_empty() {
-	    self.reader.read_line(&mut self.line_buffer)?;
-	    if self.line_buffer.is_empty() {
-	        return Ok(());
-	        }
+    /// Run `work` on every record of `path` that `selection` accepts, on
+    /// `--threads` threads, and hand the results to `consume` in file order.
+    fn each<R, W, C>(
+        &self,
+        path: &Path,
+        selection: &Selection,
+        work: W,
+        mut consume: C,
+    ) -> anyhow::Result<()>
+    where
+        R: Send,
+        W: Fn(Record) -> anyhow::Result<R> + Sync,
+        C: FnMut(R) -> anyhow::Result<()>,
+    {
+        let format = self.format(path)?;
+        anyhow::ensure!(
+            format.has_reader(),
+            "Cannot read {:?}: {} input has no feature table reader",
+            path,
+            format
+        );
+        let threads = parallel::threads(self.threads);
+        let work = |record: std::io::Result<Record>| {
+            let record = record.with_context(|| format!("Failed to read {:?}", path))?;
+            if !selection.accepts(&record) {
+                return Ok(None);
             }
-	while !self.line_buffer.is_empty() {
-	    if self.line_buffer.starts_with("LOCUS") {
-			record.rec_clear();
-	            	let mut header_fields: Vec<&str> = self.line_buffer.split_whitespace().collect();
-	                let mut header_iter = header_fields.iter();
-	                header_iter.next();
-	                record.id = header_iter.next().map(|s| s.to_string()).unwrap();
-	                let lens = header_iter.next().map(|s| s.to_string()).unwrap();
-	                record.length = lens.trim().parse::<u32>().unwrap();
-			self.line_buffer.clear();
-			}
-	    if self.line_buffer.starts_with("ORIGIN") {
-	        let mut sequences = String::new();
-	        let result_seq = loop {  
-		     self.line_buffer.clear();
-		     self.reader.read_line(&mut self.line_buffer)?;
-                     if self.line_buffer.starts_with("//") {
-		         break sequences;
-                     } else {
-	                 let s: Vec<&str> = self.line_buffer.split_whitespace().collect();
-		         let s = &s[1..];
-		         let sequence = s.iter().join("");
-		         sequences.push_str(&sequence);
-                         }     
-	             };
-		record.sequence = result_seq.to_string();
-		break;
-                }
-	 self.line_buffer.clear();
-	 self.reader.read_line(&mut self.line_buffer)?;
-	 }
-         Ok(())
-     }
+            work(record).map(Some)
+        };
+        let consume = |result: Option<R>| match result {
+            Some(result) => consume(result),
+            None => Ok(()),
+        };
+        // compressed inputs cannot be mapped and are streamed instead
+        if self.mmap && compression::sniff(path)? == Compression::None {
+            let file = MappedFile::with_format(path, format)?;
+            return parallel::map_mapped(&file, threads, work, consume);
+        }
+        let reader = compression::open(path)?;
+        parallel::map_records(reader, format, threads, work, consume)
+    }
 }
 
+/// `template` with `{file}` and `{assembly}` filled from the input path.
+fn bind_input(template: &Template, path: &Path) -> Template {
+    template
+        .bind("file", &input::file_stem(path))
+        .bind("assembly", &input::assembly(path))
+}
+
+impl OutputArgs {
+    fn compression(&self) -> Compression {
+        self.compress
+            .or_else(|| self.output.as_deref().and_then(Compression::from_path))
+            .unwrap_or(Compression::None)
+    }
 
+    fn sink(&self) -> anyhow::Result<Sink> {
+        match &self.output {
+            Some(path) => create(path, self.compression()),
+            None => Ok(Sink::stdout(self.compression())?),
+        }
+    }
+}
 
-#[derive(Default, Clone, Debug)]
-pub struct Record {
-    id: String,
-    length: u32,
-    sequence: String
+fn create(path: &Path, compression: Compression) -> anyhow::Result<Sink> {
+    Sink::create(path, compression).with_context(|| format!("Failed to create {:?}", path))
 }
 
+/// Most split fasta files kept open at once; the least recently written is
+/// finished to make room.
+const MAX_OPEN_FILES: usize = 64;
 
-impl Record {
-    /// Create a new instance.
-    pub fn new() -> Self {
-        Record {
-            id: "".to_owned(),
-            length: 0,
-            sequence: "".to_owned()
-        }
+/// Fasta output to a single sink, or to one file per record.
+enum FastaOutput {
+    Single {
+        name: String,
+        writer: Box<Writer<Sink>>,
+    },
+    Split {
+        dir: PathBuf,
+        file_name: Template,
+        compression: Compression,
+        width: usize,
+        case: Case,
+        /// How many files stay open: one when every record gets its own
+        /// file, more when the file name groups records.
+        capacity: usize,
+        /// Open files, the most recently written last.
+        open: Vec<(PathBuf, Writer<Sink>)>,
+        /// Every file started so far, appended to when reopened.
+        written: HashSet<PathBuf>,
+    },
+}
+
+impl FastaOutput {
+    fn new(
+        output: &OutputArgs,
+        batch: &BatchArgs,
+        extension: &str,
+        width: usize,
+        case: Case,
+    ) -> anyhow::Result<Self> {
+        let dir = match &batch.output_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                return Ok(FastaOutput::Single {
+                    name: match &output.output {
+                        Some(path) => path.display().to_string(),
+                        None => "-".to_string(),
+                    },
+                    writer: Box::new(Writer::new(output.sink()?, width, case)),
+                })
+            }
+        };
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let compression = output.compress.unwrap_or(Compression::None);
+        let file_name = match &batch.file_name {
+            Some(file_name) => file_name.clone(),
+            None => format!("{{accession}}.{}{}", extension, compression.extension())
+                .parse()
+                .map_err(anyhow::Error::msg)?,
+        };
+        let capacity = if file_name.uses(&["id", "accession", "version"]) {
+            1
+        } else {
+            MAX_OPEN_FILES
+        };
+        Ok(FastaOutput::Split {
+            dir,
+            file_name,
+            compression,
+            width,
+            case,
+            capacity,
+            open: Vec::new(),
+            written: HashSet::new(),
+        })
     }
-    pub fn is_empty(&mut self) -> bool {
-        self.id.is_empty() && self.length == 0
+
+    /// Writer for the entries of `record` from `input`, opening its file if
+    /// needed, and the name of what it writes to.
+    fn writer(
+        &mut self,
+        record: &Record,
+        input: &Path,
+    ) -> anyhow::Result<(&mut Writer<Sink>, String)> {
+        match self {
+            FastaOutput::Single { name, writer } => Ok((writer.as_mut(), name.clone())),
+            FastaOutput::Split {
+                dir,
+                file_name,
+                compression,
+                width,
+                case,
+                capacity,
+                open,
+                written,
+            } => {
+                let name: String = bind_input(file_name, input)
+                    .render(record, None)
+                    .chars()
+                    .map(|c| if c == '/' || c == '\\' { '_' } else { c })
+                    .collect();
+                let path = dir.join(name);
+                match open.iter().position(|(open, _)| *open == path) {
+                    Some(position) => {
+                        let file = open.remove(position);
+                        open.push(file);
+                    }
+                    None => {
+                        if open.len() >= *capacity {
+                            open.remove(0).1.into_inner()?.finish()?;
+                        }
+                        let compression = Compression::from_path(&path).unwrap_or(*compression);
+                        let sink = if written.insert(path.clone()) {
+                            create(&path, compression)?
+                        } else {
+                            Sink::append(&path, compression)
+                                .with_context(|| format!("Failed to append to {:?}", path))?
+                        };
+                        open.push((path, Writer::new(sink, *width, *case)));
+                    }
+                }
+                let (path, writer) = open.last_mut().expect("pushed above");
+                Ok((writer, path.display().to_string()))
+            }
+        }
     }
-    pub fn check(&mut self) -> Result<(), &str> {
-        if self.id().is_empty() {
-            return Err("Expecting id for Gbk record.");
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            FastaOutput::Single { writer, .. } => writer.into_inner()?.finish()?,
+            FastaOutput::Split { open, .. } => {
+                for (_, writer) in open {
+                    writer.into_inner()?.finish()?;
+                }
+            }
         }
         Ok(())
     }
-    pub fn id(&mut self) -> &str {
-        self.id.as_ref()
-    }
-    pub fn length(&mut self) -> u32 {
-        self.length
-    }   
-    pub fn sequence(&mut self) -> &str {
-        self.sequence.as_ref()
-    }
-    fn rec_clear(&mut self) {
-        self.id.clear();
-        self.length = 0;
-        self.sequence.clear();
-    }
-}
-
-/// An iterator over the records of a Fasta file.
-pub struct Records<B>
-where
-    B: io::BufRead,
-{
-    reader: Reader<B>,
-    error_has_occurred: bool,
-}
-
-
-impl<B> Iterator for Records<B>
-where
-    B: io::BufRead,
-{
-    type Item = io::Result<Record>;
-
-    fn next(&mut self) -> Option<io::Result<Record>> {
-            if self.error_has_occurred {
-	        None
-	        } else {
-                let mut record = Record::new();
-                match self.reader.read(&mut record) {
-	            Ok(()) if record.is_empty() => None,
-                    Ok(()) => Some(Ok(record)),
-		    Err(err) => {
-		        self.error_has_occurred = true;
-		        Some(Err(err))
-		        }
-                    }
+}
+
+/// Convert every input into `output`, noting protein sources in `report` and
+/// per-input counts in the manifest.
+fn write_fasta(
+    input: &InputArgs,
+    batch: &BatchArgs,
+    converter: &mut Converter,
+    mut output: FastaOutput,
+    mut report: Option<Sink>,
+) -> anyhow::Result<()> {
+    let files = input.files()?;
+    let selection = input.selection()?;
+    let mut manifest = match &batch.manifest {
+        Some(path) => {
+            let mut manifest = create(
+                path,
+                Compression::from_path(path).unwrap_or(Compression::None),
+            )?;
+            writeln!(manifest, "input\tformat\toutput\trecords\tsequences")?;
+            Some(manifest)
+        }
+        None => None,
+    };
+    for path in files {
+        let format = input.format(&path)?;
+        converter.set_prefix(
+            batch
+                .id_prefix
+                .as_ref()
+                .map(|prefix| bind_input(prefix, &path)),
+        );
+        let (mut records, mut sequences) = (0, 0);
+        let mut outputs = BTreeSet::new();
+        let preparer = converter.preparer().clone();
+        let prepare = |record: Record| {
+            let prepared = preparer.prepare(&record)?;
+            Ok((record, prepared))
+        };
+        input.each(&path, &selection, prepare, |(record, prepared)| {
+            for message in prepared.skipped() {
+                eprintln!("{}", message);
+            }
+            let (writer, name) = output.writer(&record, &path)?;
+            converter.set_output(&name);
+            let entries = converter.finish(&record, prepared)?;
+            outputs.insert(name);
+            for entry in &entries {
+                if let (Some(report), Some(source)) = (report.as_mut(), entry.source) {
+                    writeln!(report, "{}\t{}\t{}", record.id(), entry.id, source)?;
                 }
-     }
+                writer.write_record(&entry.header, &entry.sequence)?;
+            }
+            records += 1;
+            sequences += entries.len();
+            Ok(())
+        })?;
+        if let Some(manifest) = manifest.as_mut() {
+            let outputs: Vec<_> = outputs.into_iter().collect();
+            writeln!(
+                manifest,
+                "{}\t{}\t{}\t{}\t{}",
+                path.display(),
+                format,
+                outputs.join(","),
+                records,
+                sequences
+            )?;
+        }
+    }
+    output.finish()?;
+    if let Some(report) = report {
+        report.finish()?;
+    }
+    if let Some(manifest) = manifest {
+        manifest.finish()?;
+    }
+    Ok(())
 }
 
-struct Config {
-    filename: String,
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::Convert(args) => convert(args),
+        Command::Extract(args) => extract(args),
+        Command::Verify(args) => verify(args),
+        Command::Stats(args) => stats(args),
+        Command::Index(args) => index(args),
+        Command::Fetch(args) => fetch(args),
+    }
+}
+
+fn convert(args: ConvertArgs) -> anyhow::Result<()> {
+    let mut options = args.fasta.options();
+    options.annotated = args.annotated;
+    // translated_cds.faa has no stop codons
+    options.stops = args.stops.unwrap_or(if options.ncbi {
+        StopPolicy::Strip
+    } else {
+        StopPolicy::Truncate
+    });
+    // nor the pseudo CDS
+    if options.ncbi && args.to == Output::Faa && args.fasta.policies.pseudo.is_none() {
+        options.policies.pseudo = Policy::Skip;
+    }
+    let report = match &args.source_report {
+        Some(path) => {
+            let mut report = create(
+                path,
+                Compression::from_path(path).unwrap_or(Compression::None),
+            )?;
+            writeln!(report, "record\tid\tsource")?;
+            Some(report)
+        }
+        None => None,
+    };
+    let width = args.fasta.width.unwrap_or(match args.to {
+        Output::Faa if !options.ncbi => PROTEIN_WIDTH,
+        _ => NUCLEOTIDE_WIDTH,
+    });
+    let output = FastaOutput::new(
+        &args.output,
+        &args.batch,
+        &args.to.to_string(),
+        width,
+        args.fasta.case,
+    )?;
+    let mut converter = Converter::new(args.to, options);
+    write_fasta(&args.input, &args.batch, &mut converter, output, report)
+}
+
+fn extract(args: ExtractArgs) -> anyhow::Result<()> {
+    let mut options = args.fasta.options();
+    options.kinds = match args.types {
+        Some(types) => types,
+        // the filter picks the features among every key
+        None if options.filter.is_some() => Vec::new(),
+        None => vec!["CDS".to_string()],
+    };
+    options.flanks = Flanks {
+        upstream: args.upstream,
+        downstream: args.downstream,
+    };
+    options.flanks_only = args.flanks_only;
+    if args.intergenic {
+        options.intergenic = Some(IntergenicOptions {
+            strand_aware: args.strand_aware,
+            orientation: args.pairs,
+            min_length: args.min_length,
+        });
+    }
+    if options.ncbi && (options.intergenic.is_some() || !options.flanks.is_empty()) {
+        anyhow::bail!("--ncbi cannot be combined with flanks or --intergenic");
+    }
+    if args.intergenic && options.header.is_some() {
+        anyhow::bail!("--header cannot be combined with --intergenic");
+    }
+    if args.strand_aware
+        && matches!(
+            args.pairs,
+            Some(Orientation::Convergent | Orientation::Divergent)
+        )
+    {
+        anyhow::bail!("--strand-aware cannot be combined with --pairs convergent or divergent: neighbours on the same strand are always tandem");
+    }
+    let width = args.fasta.width.unwrap_or(NUCLEOTIDE_WIDTH);
+    let output = FastaOutput::new(&args.output, &args.batch, "ffn", width, args.fasta.case)?;
+    let mut converter = Converter::new(Output::Ffn, options);
+    write_fasta(&args.input, &args.batch, &mut converter, output, None)
+}
+
+fn verify(args: VerifyArgs) -> anyhow::Result<()> {
+    let options = Options {
+        policies: args.policies.policies(),
+        id_chain: args.id_chain,
+        ..Options::default()
+    };
+    let converter = Converter::new(Output::Faa, options);
+    let files = args.input.files()?;
+    let selection = args.input.selection()?;
+    let mut out = args.output.sink()?;
+    writeln!(out, "{}", verify::HEADER)?;
+    for path in files {
+        let verify = |record: Record| Ok(converter.verify(&record)?);
+        args.input
+            .each(&path, &selection, verify, |verifications| {
+                for verification in verifications {
+                    writeln!(out, "{}", verification)?;
+                }
+                Ok(())
+            })?;
+    }
+    out.finish()?;
+    Ok(())
 }
 
-impl Config {
-    fn new(args: &[String]) -> Result<Config, &str> {
-    if args.len() < 2 {
-        panic!("not enough arguments, please provide filename");
+fn stats(args: StatsArgs) -> anyhow::Result<()> {
+    let files = args.input.files()?;
+    let selection = args.input.selection()?;
+    let mut out = args.output.sink()?;
+    writeln!(out, "{}", stats::HEADER)?;
+    for path in files {
+        let summarise = |record: Record| Ok(RecordStats::new(&record));
+        args.input.each(&path, &selection, summarise, |stats| {
+            writeln!(out, "{}", stats)?;
+            Ok(())
+        })?;
     }
-    let filename = args[1].clone();
+    out.finish()?;
+    Ok(())
+}
 
-    Ok(Config { filename })
+fn index(args: IndexArgs) -> anyhow::Result<()> {
+    for path in input::expand(&args.inputs)? {
+        index::create(&path)?;
     }
+    Ok(())
 }
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let config = Config::new(&args).unwrap_or_else(|err| {
-        println!("Problem with parsing file arguments: {}", err);
-	process::exit(1);
-	});
-    let file_gbk = fs::File::open(config.filename)?;
-    let mut reader = Reader::new(file_gbk);
-    for result in reader.records() {
-        let record = result.expect("err");
-	println!(">{}_{:?}\n{}", record.id, record.length, record.sequence);
-    } 
+fn fetch(args: FetchArgs) -> anyhow::Result<()> {
+    let mut reader = IndexedReader::from_file(&args.file)?;
+    if let Some(format) = args.to {
+        return fetch_records(&mut reader, &args, format);
+    }
+    let mut out = Writer::new(args.output.sink()?, args.width, Case::Preserve);
+    for region in &args.regions {
+        match region.span() {
+            Some((start, end)) => {
+                let sequence = reader.fetch_sequence(&region.name, start, end)?;
+                out.write_record(&region.to_string(), &sequence)?;
+            }
+            None => out.write_raw(&reader.fetch_raw(&region.name)?)?,
+        }
+    }
+    out.into_inner()?.finish()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Write the fetched records, sliced to their ranges, as GenBank or EMBL.
+fn fetch_records(
+    reader: &mut IndexedReader<Source>,
+    args: &FetchArgs,
+    format: Format,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        matches!(format, Format::Genbank | Format::Embl),
+        "Cannot write {} records; use --to genbank or --to embl",
+        format
+    );
+    let mut out = BufWriter::new(args.output.sink()?);
+    for region in &args.regions {
+        let mut record = reader
+            .fetch(&region.name)
+            .with_context(|| format!("Failed to read {} from {:?}", region.name, args.file))?;
+        if let Some((start, end)) = region.span() {
+            record = record.slice(start as usize, end as usize)?;
+        }
+        match format {
+            Format::Embl => embl::write(&mut out, &record)?,
+            _ => gbk::write(&mut out, &record)?,
+        }
+    }
+    out.into_inner().map_err(|err| err.into_error())?.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("annots").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_subcommands_and_their_defaults() {
+        match parse(&["convert", "--to", "faa", "a.gbk", "b.gbk"])
+            .unwrap()
+            .command
+        {
+            Command::Convert(args) => {
+                assert_eq!(args.to, Output::Faa);
+                assert_eq!(
+                    args.input.inputs,
+                    vec![PathBuf::from("a.gbk"), PathBuf::from("b.gbk")]
+                );
+                assert_eq!(args.input.threads, 1);
+                assert!(args.stops.is_none());
+            }
+            _ => panic!("expected convert"),
+        }
+        match parse(&["extract", "--types", "tRNA,rRNA", "a.gbk"])
+            .unwrap()
+            .command
+        {
+            Command::Extract(args) => {
+                assert_eq!(
+                    args.types,
+                    Some(vec!["tRNA".to_string(), "rRNA".to_string()])
+                );
+            }
+            _ => panic!("expected extract"),
+        }
+    }
+
+    const RECORD: &str = "LOCUS       NC_1  18 bp    DNA     linear   BCT 01-JAN-2020\n\
+                          ACCESSION   NC_1\n\
+                          VERSION     NC_1.1\n\
+                          FEATURES             Location/Qualifiers\n\
+                          \x20    CDS             1..9\n\
+                          \x20                    /locus_tag=\"T_1\"\n\
+                          \x20                    /protein_id=\"WP_1.1\"\n\
+                          \x20    CDS             10..18\n\
+                          \x20                    /locus_tag=\"T_1\"\n\
+                          ORIGIN\n\
+                          \x20       1 atgaaataga tgccctaa\n\
+                          //\n";
+
+    /// An empty directory of its own for each test.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("annots-main-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// The same genome as plain and gzipped GenBank, both named after one
+    /// assembly.
+    fn inputs(dir: &Path) -> [PathBuf; 2] {
+        let plain = dir.join("GCF_000002.1_y.gbff");
+        fs::write(&plain, RECORD).unwrap();
+        let gzipped = dir.join("GCF_000002.1_y.gbff.gz");
+        let mut sink = Sink::create(&gzipped, Compression::Gzip).unwrap();
+        sink.write_all(RECORD.as_bytes()).unwrap();
+        sink.finish().unwrap();
+        [plain, gzipped]
+    }
+
+    fn run(args: &[&str]) {
+        match parse(args).unwrap().command {
+            Command::Convert(args) => convert(args).unwrap(),
+            _ => panic!("expected convert"),
+        }
+    }
+
+    fn headers(path: &Path) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix('>'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn inputs_sharing_an_output_file_share_its_ids() {
+        let dir = scratch("collision");
+        let [plain, gzipped] = inputs(&dir);
+        let out = dir.join("out");
+        let (plain, gzipped, out) = (
+            plain.to_str().unwrap(),
+            gzipped.to_str().unwrap(),
+            out.to_str().unwrap(),
+        );
+        run(&[
+            "convert",
+            "--to",
+            "ffn",
+            "--output-dir",
+            out,
+            "--file-name",
+            "{assembly}.ffn",
+            plain,
+            gzipped,
+        ]);
+        assert_eq!(
+            headers(&dir.join("out/GCF_000002.1.ffn")),
+            vec!["T_1", "T_1_2", "T_1_3", "T_1_4"]
+        );
+        run(&[
+            "convert",
+            "--to",
+            "faa",
+            "--ncbi",
+            "--output-dir",
+            out,
+            "--file-name",
+            "{assembly}.faa",
+            plain,
+            gzipped,
+        ]);
+        let ids: Vec<String> = headers(&dir.join("out/GCF_000002.1.faa"))
+            .into_iter()
+            .map(|header| header.split(' ').next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "lcl|NC_1.1_prot_WP_1.1_1",
+                "lcl|NC_1.1_prot_2",
+                "lcl|NC_1.1_prot_WP_1.1_3",
+                "lcl|NC_1.1_prot_4",
+            ]
+        );
+        // each prefix counts on its own
+        let other = dir.join("GCF_000003.1_z.gbff");
+        fs::write(&other, RECORD).unwrap();
+        run(&[
+            "convert",
+            "--to",
+            "ffn",
+            "--output-dir",
+            out,
+            "--file-name",
+            "all.ffn",
+            "--id-prefix",
+            "{assembly}|",
+            plain,
+            other.to_str().unwrap(),
+        ]);
+        assert_eq!(
+            headers(&dir.join("out/all.ffn")),
+            vec![
+                "GCF_000002.1|T_1",
+                "GCF_000002.1|T_1_2",
+                "GCF_000003.1|T_1",
+                "GCF_000003.1|T_1_2",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lists_each_input_in_the_manifest() {
+        let dir = scratch("manifest");
+        let [plain, gzipped] = inputs(&dir);
+        let out = dir.join("out");
+        let manifest = dir.join("manifest.tsv");
+        run(&[
+            "convert",
+            "--to",
+            "faa",
+            "--output-dir",
+            out.to_str().unwrap(),
+            "--manifest",
+            manifest.to_str().unwrap(),
+            plain.to_str().unwrap(),
+            gzipped.to_str().unwrap(),
+        ]);
+        let output = out.join("NC_1.faa");
+        assert_eq!(
+            fs::read_to_string(&manifest).unwrap(),
+            format!(
+                "input\tformat\toutput\trecords\tsequences\n\
+                 {}\tgenbank\t{}\t1\t2\n\
+                 {}\tgenbank\t{}\t1\t2\n",
+                plain.display(),
+                output.display(),
+                gzipped.display(),
+                output.display()
+            )
+        );
+        assert_eq!(headers(&output), vec!["T_1", "T_1_2", "T_1_3", "T_1_4"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        for args in [
+            &["convert"][..],
+            &["convert", "--file-name", "{assembly}.faa", "a.gbk"],
+            &["convert", "-o", "a.fna", "--output-dir", "out", "a.gbk"],
+            &["extract", "--intergenic", "--upstream", "5", "a.gbk"],
+            &["extract", "--strand-aware", "a.gbk"],
+            &["convert", "--to", "gff", "a.gbk"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+}
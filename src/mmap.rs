@@ -0,0 +1,282 @@
+use crate::compression::Compression;
+use crate::gbk::GbkRead;
+use crate::input::Format;
+use crate::record::Record;
+use crate::{embl, gbk};
+use anyhow::Context;
+use memchr::{memchr, memmem};
+use memmap2::Mmap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::str;
+
+/// Bytes looked at to recognise the format of a mapped file.
+const HEAD: usize = 8192;
+
+/// An uncompressed GenBank or EMBL file mapped into memory, with the byte
+/// range of every record, so records can be read in any order and only
+/// while they are needed.
+pub struct MappedFile {
+    map: Mmap,
+    format: Format,
+    records: Vec<Range<usize>>,
+}
+
+impl MappedFile {
+    /// Map `path`, recognising its format from the first bytes.
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let map = map(path)?;
+        let format = Format::detect(&map[..map.len().min(HEAD)])
+            .with_context(|| format!("Could not recognise the format of {:?}", path))?;
+        MappedFile::new(path, map, format)
+    }
+
+    /// Map `path` and read it as `format`.
+    pub fn with_format<P: AsRef<Path>>(path: P, format: Format) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        MappedFile::new(path, map(path)?, format)
+    }
+
+    fn new(path: &Path, map: Mmap, format: Format) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            format.has_reader(),
+            "Cannot read {:?}: {} input has no feature table reader",
+            path,
+            format
+        );
+        let records = record_ranges(&map);
+        Ok(MappedFile {
+            map,
+            format,
+            records,
+        })
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Number of records in the file.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The record at `index`, in file order.
+    pub fn get(&self, index: usize) -> Option<RawRecord<'_>> {
+        self.records.get(index).map(|range| RawRecord {
+            bytes: &self.map[range.clone()],
+            format: self.format,
+        })
+    }
+
+    /// Every record, unparsed, in file order.
+    pub fn iter(&self) -> impl Iterator<Item = RawRecord<'_>> + '_ {
+        (0..self.len()).filter_map(move |index| self.get(index))
+    }
+
+    /// Every record parsed, as the streaming readers yield them.
+    pub fn records(&self) -> impl Iterator<Item = io::Result<Record>> + '_ {
+        self.iter().map(|raw| raw.parse())
+    }
+}
+
+/// The bytes of one record in a mapped file, from its `LOCUS` or `ID` line
+/// to its `//` line.
+#[derive(Clone, Copy, Debug)]
+pub struct RawRecord<'a> {
+    bytes: &'a [u8],
+    format: Format,
+}
+
+impl<'a> RawRecord<'a> {
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Name on the first line, read without parsing the record.
+    pub fn id(&self) -> Option<&'a str> {
+        let start = self.bytes.iter().position(|b| !b.is_ascii_whitespace())?;
+        let line = &self.bytes[start..];
+        let line = &line[..memchr(b'\n', line).unwrap_or(line.len())];
+        let id = str::from_utf8(line).ok()?.split_whitespace().nth(1)?;
+        Some(id.trim_end_matches(';'))
+    }
+
+    /// Parse the record with the reader for its format.
+    pub fn parse(&self) -> io::Result<Record> {
+        let mut record = Record::new();
+        match self.format {
+            Format::Embl => embl::Reader::from_bufread(self.bytes).read(&mut record)?,
+            _ => gbk::Reader::from_bufread(self.bytes).read(&mut record)?,
+        }
+        if record.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No {} record in {:?}", self.format, self.head()),
+            ));
+        }
+        Ok(record)
+    }
+
+    /// Start of the first line, for error messages.
+    fn head(&self) -> String {
+        let line = self.bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+        String::from_utf8_lossy(&line[..line.len().min(80)]).into_owned()
+    }
+}
+
+fn map(path: &Path) -> anyhow::Result<Mmap> {
+    let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    // SAFETY: the map is only read. As with any mapped file, another process
+    // truncating it while we read is outside what we can guard against.
+    let map = unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map {:?}", path))?;
+    let compression = Compression::detect(&map[..map.len().min(HEAD)]);
+    anyhow::ensure!(
+        compression == Compression::None,
+        "Cannot map {:?}: {} input has to be decompressed first",
+        path,
+        compression
+    );
+    Ok(map)
+}
+
+/// Byte ranges of the records in `bytes`, each ending after its `//` line.
+fn record_ranges(bytes: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut push = |range: Range<usize>| {
+        if !bytes[range.clone()].iter().all(u8::is_ascii_whitespace) {
+            ranges.push(range);
+        }
+    };
+    for found in memmem::find_iter(bytes, b"\n//") {
+        let line = found + 1;
+        let end = memchr(b'\n', &bytes[line..]).map_or(bytes.len(), |i| line + i + 1);
+        push(start..end);
+        start = end;
+    }
+    push(start..bytes.len());
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn genbank(id: &str) -> String {
+        format!(
+            "LOCUS       {}  6 bp    DNA     linear   BCT 01-JAN-2020\n\
+             FEATURES             Location/Qualifiers\n\
+             \x20    CDS             1..6\n\
+             \x20                    /note=\"//\"\n\
+             ORIGIN\n\
+             \x20       1 atgtaa\n\
+             //\n",
+            id
+        )
+    }
+
+    /// `data` written to a file of its own for each test.
+    fn file(name: &str, data: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("annots-mmap-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn finds_record_ranges() {
+        let text = b"LOCUS a\n//\n\nLOCUS b\nORIGIN\n//\nLOCUS c\n//";
+        let ranges: Vec<&[u8]> = record_ranges(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                &b"LOCUS a\n//\n"[..],
+                b"\nLOCUS b\nORIGIN\n//\n",
+                b"LOCUS c\n//"
+            ]
+        );
+        assert!(record_ranges(b"\n\n").is_empty());
+    }
+
+    #[test]
+    fn reads_records_as_the_streaming_reader_does() {
+        let text = [genbank("R1"), genbank("R2"), genbank("R3")].concat();
+        let path = file("records.gbk", text.as_bytes());
+        let mapped = MappedFile::open(&path).unwrap();
+        assert_eq!(mapped.format(), Format::Genbank);
+        assert_eq!(mapped.len(), 3);
+        assert_eq!(mapped.get(1).unwrap().id(), Some("R2"));
+        assert_eq!(mapped.get(1).unwrap().bytes(), genbank("R2").as_bytes());
+        assert!(mapped.get(3).is_none());
+        let mapped: Vec<String> = mapped
+            .records()
+            .map(|record| format!("{:?}", record.unwrap()))
+            .collect();
+        let streamed: Vec<String> = gbk::Reader::from_bufread(text.as_bytes())
+            .records()
+            .map(|record| format!("{:?}", record.unwrap()))
+            .collect();
+        assert_eq!(mapped, streamed);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn names_embl_records() {
+        let path = file(
+            "record.embl",
+            b"ID   X56734; SV 1; linear; DNA; STD; PRO; 6 BP.\nSQ   Sequence 6 BP;\n     atgtaa 6\n//\n",
+        );
+        let mapped = MappedFile::open(&path).unwrap();
+        assert_eq!(mapped.format(), Format::Embl);
+        assert_eq!(mapped.get(0).unwrap().id(), Some("X56734"));
+        assert_eq!(
+            mapped.get(0).unwrap().parse().unwrap().sequence(),
+            b"atgtaa"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuses_compressed_and_unreadable_files() {
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzipped.write_all(genbank("R1").as_bytes()).unwrap();
+        let path = file("records.gbk.gz", &gzipped.finish().unwrap());
+        let error = MappedFile::open(&path).err().unwrap().to_string();
+        assert!(
+            error.ends_with("gzip input has to be decompressed first"),
+            "{}",
+            error
+        );
+        fs::remove_file(&path).unwrap();
+
+        let path = file("records.fa", b">R1\nATG\n");
+        let error = MappedFile::open(&path).err().unwrap().to_string();
+        assert!(
+            error.ends_with("fasta input has no feature table reader"),
+            "{}",
+            error
+        );
+        let error = MappedFile::with_format(&path, Format::Genbank)
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .parse()
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "No genbank record in \">R1\"");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use crate::policy::{is_pseudo, partial_ends};
+use crate::record::{Feature, Record};
+
+/// Which NCBI Datasets file the header should match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NcbiFile {
+    /// `cds_from_genomic.fna`, or `rna_from_genomic.fna` for RNA features.
+    Genomic,
+    /// `translated_cds.faa`.
+    Translated,
+}
+
+/// Build an NCBI style header such as
+/// `lcl|NC_000913.3_cds_NP_414542.1_1 [gene=thrL] [locus_tag=b0001]
+/// [protein=thr operon leader peptide] [protein_id=NP_414542.1]
+/// [location=190..255] [gbkey=CDS]`, where `index` is the 1-based position
+/// of the feature among those written from the file.
+pub fn ncbi_header(record: &Record, feature: &Feature, file: NcbiFile, index: usize) -> String {
+    let kind = match (file, feature.kind.as_str()) {
+        (NcbiFile::Translated, _) => "prot".to_string(),
+        (_, "CDS") => "cds".to_string(),
+        (_, key) if key.ends_with("RNA") => "rna".to_string(),
+        (_, key) => key.to_lowercase(),
+    };
+    let mut header = format!("lcl|{}_{}_", record.version(), kind);
+    if let Some(protein_id) = feature.qualifier("protein_id") {
+        header.push_str(protein_id);
+        header.push('_');
+    }
+    header.push_str(&index.to_string());
+    let mut tag = |key: &str, value: &str| {
+        if !value.is_empty() {
+            header.push_str(&format!(" [{}={}]", key, value));
+        }
+    };
+    tag("gene", feature.qualifier("gene").unwrap_or_default());
+    tag(
+        "locus_tag",
+        feature.qualifier("locus_tag").unwrap_or_default(),
+    );
+    let db_xrefs: Vec<&str> = feature
+        .qualifiers
+        .iter()
+        .filter(|(k, _)| k == "db_xref")
+        .map(|(_, v)| v.as_str())
+        .collect();
+    tag("db_xref", &db_xrefs.join(","));
+    if feature.kind == "CDS" {
+        tag("protein", feature.qualifier("product").unwrap_or_default());
+    } else {
+        tag("product", feature.qualifier("product").unwrap_or_default());
+    }
+    if is_pseudo(feature) {
+        tag("pseudo", "true");
+    }
+    if feature.codon_offset() > 0 {
+        tag("frame", &(feature.codon_offset() + 1).to_string());
+    }
+    tag("partial", partial_ends(feature).unwrap_or_default());
+    tag(
+        "exception",
+        feature.qualifier("exception").unwrap_or_default(),
+    );
+    tag(
+        "transl_except",
+        feature.qualifier("transl_except").unwrap_or_default(),
+    );
+    tag(
+        "protein_id",
+        feature.qualifier("protein_id").unwrap_or_default(),
+    );
+    tag("location", &feature.location.to_string());
+    tag("gbkey", &feature.kind);
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+
+    fn feature(kind: &str, location: &str, qualifiers: &[(&str, &str)]) -> Feature {
+        Feature {
+            kind: kind.to_string(),
+            location: Location::parse(location).unwrap(),
+            qualifiers: qualifiers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    fn record() -> Record {
+        Record {
+            id: "NC_000913".to_string(),
+            version: "NC_000913.3".to_string(),
+            ..Record::default()
+        }
+    }
+
+    #[test]
+    fn matches_cds_from_genomic_and_translated_cds() {
+        let cds = feature(
+            "CDS",
+            "190..255",
+            &[
+                ("gene", "thrL"),
+                ("locus_tag", "b0001"),
+                ("db_xref", "UniProtKB/Swiss-Prot:P0AD86"),
+                ("db_xref", "GeneID:944742"),
+                ("product", "thr operon leader peptide"),
+                ("protein_id", "NP_414542.1"),
+            ],
+        );
+        let tags = " [gene=thrL] [locus_tag=b0001] \
+                    [db_xref=UniProtKB/Swiss-Prot:P0AD86,GeneID:944742] \
+                    [protein=thr operon leader peptide] [protein_id=NP_414542.1] \
+                    [location=190..255] [gbkey=CDS]";
+        assert_eq!(
+            ncbi_header(&record(), &cds, NcbiFile::Genomic, 1),
+            format!("lcl|NC_000913.3_cds_NP_414542.1_1{}", tags)
+        );
+        assert_eq!(
+            ncbi_header(&record(), &cds, NcbiFile::Translated, 1),
+            format!("lcl|NC_000913.3_prot_NP_414542.1_1{}", tags)
+        );
+    }
+
+    #[test]
+    fn marks_pseudo_partial_and_frame() {
+        let cds = feature(
+            "CDS",
+            "complement(<1..>100)",
+            &[("locus_tag", "b0002"), ("pseudo", ""), ("codon_start", "2")],
+        );
+        assert_eq!(
+            ncbi_header(&record(), &cds, NcbiFile::Genomic, 7),
+            "lcl|NC_000913.3_cds_7 [locus_tag=b0002] [pseudo=true] [frame=2] \
+             [partial=5',3'] [location=complement(<1..>100)] [gbkey=CDS]"
+        );
+    }
+
+    #[test]
+    fn names_rna_and_other_features_by_their_key() {
+        let rrna = feature("rRNA", "1..10", &[("product", "16S ribosomal RNA")]);
+        assert_eq!(
+            ncbi_header(&record(), &rrna, NcbiFile::Genomic, 3),
+            "lcl|NC_000913.3_rna_3 [product=16S ribosomal RNA] [location=1..10] [gbkey=rRNA]"
+        );
+        let repeat = feature("repeat_region", "1..10", &[]);
+        assert_eq!(
+            ncbi_header(&record(), &repeat, NcbiFile::Genomic, 4),
+            "lcl|NC_000913.3_repeat_region_4 [location=1..10] [gbkey=repeat_region]"
+        );
+    }
+}
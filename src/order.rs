@@ -0,0 +1,184 @@
+use crate::record::Feature;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Order in which the features of a record are written.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum SortOrder {
+    /// As they appear in the feature table.
+    #[default]
+    File,
+    /// By start, then end, of the location as written, so a feature across
+    /// the origin such as `join(2900..3000,1..50)` sorts at 2900.
+    Coordinate,
+    /// Lexically by id, so `TAG_10` comes before `TAG_2`.
+    Id,
+    /// By id with runs of digits compared as numbers, so `TAG_2` comes
+    /// before `TAG_10`.
+    Natural,
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SortOrder::File => "file",
+            SortOrder::Coordinate => "coordinate",
+            SortOrder::Id => "id",
+            SortOrder::Natural => "natural",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "file" | "genomic" => Ok(SortOrder::File),
+            "coordinate" | "position" => Ok(SortOrder::Coordinate),
+            "id" => Ok(SortOrder::Id),
+            "natural" => Ok(SortOrder::Natural),
+            _ => Err(format!("unknown sort order {:?}", s)),
+        }
+    }
+}
+
+impl SortOrder {
+    /// Sort `(id, feature, payload)` entries in place. The sort is stable, so
+    /// ties keep their file order.
+    pub fn sort<T>(&self, entries: &mut [(String, &Feature, T)]) {
+        match self {
+            SortOrder::File => {}
+            SortOrder::Coordinate => entries.sort_by_key(|(_, feature, _)| {
+                (
+                    feature.location.written_start(),
+                    feature.location.written_end(),
+                )
+            }),
+            SortOrder::Id => entries.sort_by(|a, b| a.0.cmp(&b.0)),
+            SortOrder::Natural => entries.sort_by(|a, b| natural_cmp(&a.0, &b.0)),
+        }
+    }
+}
+
+/// Compare strings treating each run of ASCII digits as a number.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x_digits, x_rest) = split_digits(a);
+                let (y_digits, y_rest) = split_digits(b);
+                let (x_trimmed, y_trimmed) = (trim_zeros(x_digits), trim_zeros(y_digits));
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x_digits.len().cmp(&y_digits.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = x_rest;
+                b = y_rest;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+    let end = s
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let start = digits
+        .iter()
+        .position(|&c| c != b'0')
+        .unwrap_or(digits.len());
+    &digits[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+
+    fn feature(location: &str) -> Feature {
+        Feature {
+            kind: "CDS".to_string(),
+            location: Location::parse(location).unwrap(),
+            qualifiers: Vec::new(),
+        }
+    }
+
+    fn sorted(order: &str, entries: &[(&str, &str)]) -> Vec<String> {
+        let features: Vec<Feature> = entries.iter().map(|(_, l)| feature(l)).collect();
+        let mut entries: Vec<(String, &Feature, ())> = entries
+            .iter()
+            .zip(&features)
+            .map(|((id, _), feature)| (id.to_string(), feature, ()))
+            .collect();
+        order.parse::<SortOrder>().unwrap().sort(&mut entries);
+        entries.into_iter().map(|(id, _, _)| id).collect()
+    }
+
+    const ENTRIES: [(&str, &str); 5] = [
+        ("TAG_10", "join(2951..3000,1..49)"),
+        ("TAG_2", "complement(join(101..200,301..400))"),
+        ("TAG_1", "101..150"),
+        ("tag_3", "complement(join(2901..3000,1..20))"),
+        ("TAG_02", "1000..2000"),
+    ];
+
+    #[test]
+    fn sorts_by_file_order_coordinate_and_id() {
+        assert_eq!(
+            sorted("genomic", &ENTRIES),
+            vec!["TAG_10", "TAG_2", "TAG_1", "tag_3", "TAG_02"]
+        );
+        assert_eq!(
+            sorted("position", &ENTRIES),
+            vec!["TAG_1", "TAG_2", "TAG_02", "tag_3", "TAG_10"]
+        );
+        assert_eq!(
+            sorted("id", &ENTRIES),
+            vec!["TAG_02", "TAG_1", "TAG_10", "TAG_2", "tag_3"]
+        );
+        assert_eq!(
+            sorted("Natural", &ENTRIES),
+            vec!["TAG_1", "TAG_2", "TAG_02", "TAG_10", "tag_3"]
+        );
+        assert!("size".parse::<SortOrder>().is_err());
+    }
+
+    #[test]
+    fn compares_digit_runs_as_numbers() {
+        for (a, b) in [
+            ("TAG_2", "TAG_10"),
+            ("TAG_2", "TAG_02"),
+            ("TAG_9a", "TAG_9b"),
+            ("TAG", "TAG_1"),
+            ("A1", "a1"),
+            ("x99999999999999999999999", "x100000000000000000000000"),
+        ] {
+            assert_eq!(natural_cmp(a, b), Ordering::Less, "{} < {}", a, b);
+            assert_eq!(natural_cmp(b, a), Ordering::Greater, "{} > {}", b, a);
+        }
+        assert_eq!(natural_cmp("TAG_10", "TAG_10"), Ordering::Equal);
+    }
+}
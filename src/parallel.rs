@@ -0,0 +1,287 @@
+use crate::input::{self, Format};
+use crate::mmap::MappedFile;
+use crate::record::Record;
+use crossbeam_channel::bounded;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::iter;
+use std::thread;
+
+/// Bytes of records read into each chunk handed to a worker.
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// Chunks of results held per thread, between reading and writing.
+const IN_FLIGHT: usize = 4;
+
+/// Number of worker threads for a `--threads` value, 0 meaning every core.
+pub fn threads(requested: usize) -> usize {
+    match requested {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// Splits a flat file into chunks of whole records, each ending with a `//`
+/// line, so that they can be parsed apart from one another.
+pub struct Chunks<B> {
+    reader: B,
+    size: usize,
+}
+
+impl<B: BufRead> Chunks<B> {
+    /// Chunks of at least `size` bytes, or one record when records are larger.
+    pub fn new(reader: B, size: usize) -> Self {
+        Chunks { reader, size }
+    }
+}
+
+impl<B: BufRead> Iterator for Chunks<B> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::with_capacity(self.size);
+        loop {
+            let start = chunk.len();
+            match self.reader.read_until(b'\n', &mut chunk) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => return Some(Err(err)),
+            }
+            if chunk[start..].starts_with(b"//") && chunk.len() >= self.size {
+                break;
+            }
+        }
+        if chunk.iter().all(u8::is_ascii_whitespace) {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    }
+}
+
+/// Run `work` on each of `items` on `threads` threads and hand the results to
+/// `consume` in the order of `items`. Only a few chunks per thread are held at
+/// once, and the first error from `consume` stops the pipeline.
+pub fn ordered<I, T, R, E, W, C>(items: I, threads: usize, work: W, mut consume: C) -> Result<(), E>
+where
+    I: Iterator<Item = T> + Send,
+    T: Send,
+    R: Send,
+    W: Fn(T) -> R + Sync,
+    C: FnMut(R) -> Result<(), E>,
+{
+    let threads = threads.max(1);
+    let (slot_tx, slot_rx) = bounded(threads * IN_FLIGHT);
+    for _ in 0..threads * IN_FLIGHT {
+        slot_tx.send(()).expect("slot channel is open");
+    }
+    let (task_tx, task_rx) = bounded::<(usize, T)>(threads);
+    let (done_tx, done_rx) = bounded::<(usize, R)>(threads);
+    let work = &work;
+    thread::scope(move |scope| {
+        scope.spawn(move || {
+            for task in items.enumerate() {
+                if slot_rx.recv().is_err() || task_tx.send(task).is_err() {
+                    break;
+                }
+            }
+        });
+        for _ in 0..threads {
+            let (task_rx, done_tx) = (task_rx.clone(), done_tx.clone());
+            scope.spawn(move || {
+                for (index, task) in task_rx {
+                    if done_tx.send((index, work(task))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop((task_rx, done_tx));
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, result) in done_rx {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next) {
+                next += 1;
+                consume(result)?;
+                let _ = slot_tx.send(());
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Read the records of `reader` and run `work` on each, handing the results
+/// to `consume` in file order. With more than one thread the input is split
+/// into chunks of records that are parsed and worked on in parallel.
+pub fn map_records<B, R, W, C>(
+    reader: B,
+    format: Format,
+    threads: usize,
+    work: W,
+    mut consume: C,
+) -> anyhow::Result<()>
+where
+    B: BufRead + Send + 'static,
+    R: Send,
+    W: Fn(io::Result<Record>) -> anyhow::Result<R> + Sync,
+    C: FnMut(R) -> anyhow::Result<()>,
+{
+    if threads <= 1 {
+        for record in input::records(reader, format)? {
+            consume(work(record)?)?;
+        }
+        return Ok(());
+    }
+    let chunks = Chunks::new(reader, CHUNK_SIZE);
+    // results up to and including the first error, so that a bad record
+    // stops the output where a serial run would
+    let parse = |chunk: io::Result<Vec<u8>>| -> Vec<anyhow::Result<R>> {
+        let records = match chunk.map(|chunk| input::records(io::Cursor::new(chunk), format)) {
+            Ok(Ok(records)) => records,
+            Ok(Err(err)) => return vec![Err(err)],
+            Err(err) => Box::new(iter::once(Err(err))),
+        };
+        let mut results = Vec::new();
+        for record in records {
+            let result = work(record);
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        results
+    };
+    ordered(chunks, threads, parse, |results| {
+        for result in results {
+            consume(result?)?;
+        }
+        Ok(())
+    })
+}
+
+/// `map_records` for a mapped file, whose records are handed to the threads
+/// one at a time without being copied.
+pub fn map_mapped<R, W, C>(
+    file: &MappedFile,
+    threads: usize,
+    work: W,
+    mut consume: C,
+) -> anyhow::Result<()>
+where
+    R: Send,
+    W: Fn(io::Result<Record>) -> anyhow::Result<R> + Sync,
+    C: FnMut(R) -> anyhow::Result<()>,
+{
+    if threads <= 1 {
+        for record in file.records() {
+            consume(work(record)?)?;
+        }
+        return Ok(());
+    }
+    ordered(
+        file.iter(),
+        threads,
+        |raw| work(raw.parse()),
+        |result| consume(result?),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn hands_results_back_in_order() {
+        let mut seen = Vec::new();
+        let result: Result<(), ()> = ordered(
+            0..200u64,
+            4,
+            |n| {
+                // later items often finish first
+                thread::sleep(Duration::from_micros((200 - n) % 7 * 50));
+                n * 2
+            },
+            |n| {
+                seen.push(n);
+                Ok(())
+            },
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(seen, (0..200).map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let mut seen = Vec::new();
+        let result = ordered(
+            0..1000,
+            3,
+            |n| n,
+            |n| {
+                if n == 10 {
+                    return Err(n);
+                }
+                seen.push(n);
+                Ok(())
+            },
+        );
+        assert_eq!(result, Err(10));
+        assert_eq!(seen, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn chunks_end_with_whole_records() {
+        let text = b"LOCUS a\n//\nLOCUS b\nORIGIN\n//\nLOCUS c\n//\n\n";
+        let chunks: Vec<Vec<u8>> = Chunks::new(&text[..], 12)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            chunks,
+            vec![
+                b"LOCUS a\n//\nLOCUS b\nORIGIN\n//\n".to_vec(),
+                b"LOCUS c\n//\n\n".to_vec(),
+            ]
+        );
+        assert!(Chunks::new(&b"\n \n"[..], 12).next().is_none());
+    }
+
+    #[test]
+    fn reads_records_in_file_order_on_any_number_of_threads() {
+        // enough records for several chunks
+        let mut text = String::new();
+        for i in 0..700 {
+            text.push_str(&format!(
+                "LOCUS       R{}  3000 bp    DNA     linear   BCT 01-JAN-2020\nORIGIN\n",
+                i
+            ));
+            for line in 0..50 {
+                text.push_str(&format!(
+                    "{:>10} {}\n",
+                    line * 60 + 1,
+                    "acgtacgtac ".repeat(6)
+                ));
+            }
+            text.push_str("//\n");
+        }
+        assert!(text.len() > 2 * CHUNK_SIZE);
+        let expected: Vec<String> = (0..700).map(|i| format!("R{}", i)).collect();
+        for threads in [1, 2, 8] {
+            let mut ids = Vec::new();
+            map_records(
+                io::Cursor::new(text.clone().into_bytes()),
+                Format::Genbank,
+                threads,
+                |record| Ok(record?.id().to_string()),
+                |id| {
+                    ids.push(id);
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(ids, expected, "{} threads", threads);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::record::Feature;
+use std::str::FromStr;
+
+/// How to treat one category of unusual CDS in the output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    Skip,
+    Include,
+    /// Include, and mark the category in the FASTA header.
+    Flag,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Policy::Skip),
+            "include" => Ok(Policy::Include),
+            "flag" => Ok(Policy::Flag),
+            _ => Err(format!(
+                "unknown policy {:?}, expected skip, include or flag",
+                s
+            )),
+        }
+    }
+}
+
+/// Policies for pseudogenes, partial CDS and ribosomal slippage.
+#[derive(Clone, Copy, Debug)]
+pub struct Policies {
+    pub pseudo: Policy,
+    pub partial: Policy,
+    pub slippage: Policy,
+}
+
+impl Default for Policies {
+    fn default() -> Self {
+        Policies {
+            pseudo: Policy::Include,
+            partial: Policy::Include,
+            slippage: Policy::Include,
+        }
+    }
+}
+
+pub fn is_pseudo(feature: &Feature) -> bool {
+    feature.has_qualifier("pseudo") || feature.has_qualifier("pseudogene")
+}
+
+pub fn is_slippage(feature: &Feature) -> bool {
+    feature.has_qualifier("ribosomal_slippage")
+}
+
+/// `5'`, `3'` or `5',3'` for a partial feature.
+pub fn partial_ends(feature: &Feature) -> Option<&'static str> {
+    let location = &feature.location;
+    match (
+        location.five_prime_partial(),
+        location.three_prime_partial(),
+    ) {
+        (true, true) => Some("5',3'"),
+        (true, false) => Some("5'"),
+        (false, true) => Some("3'"),
+        (false, false) => None,
+    }
+}
+
+impl Policies {
+    /// Header flags for the feature, or `None` when it should be skipped.
+    /// The flags are empty for an ordinary CDS.
+    pub fn flags(&self, feature: &Feature) -> Option<String> {
+        let mut flags = String::new();
+        if is_pseudo(feature) {
+            match self.pseudo {
+                Policy::Skip => return None,
+                Policy::Include => {}
+                Policy::Flag => flags.push_str(" [pseudo=true]"),
+            }
+        }
+        if let Some(ends) = partial_ends(feature) {
+            match self.partial {
+                Policy::Skip => return None,
+                Policy::Include => {}
+                Policy::Flag => flags.push_str(&format!(" [partial={}]", ends)),
+            }
+        }
+        if is_slippage(feature) {
+            match self.slippage {
+                Policy::Skip => return None,
+                Policy::Include => {}
+                Policy::Flag => flags.push_str(" [ribosomal_slippage=true]"),
+            }
+        }
+        Some(flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+
+    fn cds(location: &str, qualifiers: &[&str]) -> Feature {
+        Feature {
+            kind: "CDS".to_string(),
+            location: Location::parse(location).unwrap(),
+            qualifiers: qualifiers
+                .iter()
+                .map(|k| (k.to_string(), String::new()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn partial_ends_follow_the_strand() {
+        assert_eq!(partial_ends(&cds("<1..90", &[])), Some("5'"));
+        assert_eq!(partial_ends(&cds("complement(<1..90)", &[])), Some("3'"));
+        assert_eq!(partial_ends(&cds("<1..>90", &[])), Some("5',3'"));
+        assert_eq!(partial_ends(&cds("1..90", &[])), None);
+    }
+
+    #[test]
+    fn flags_skips_or_includes_each_category() {
+        let odd = cds("<1..90", &["pseudogene", "ribosomal_slippage"]);
+        let flag = Policies {
+            pseudo: Policy::Flag,
+            partial: Policy::Flag,
+            slippage: Policy::Flag,
+        };
+        assert_eq!(
+            flag.flags(&odd).unwrap(),
+            " [pseudo=true] [partial=5'] [ribosomal_slippage=true]"
+        );
+        assert_eq!(Policies::default().flags(&odd).unwrap(), "");
+        for skip in [
+            Policies {
+                pseudo: Policy::Skip,
+                ..flag
+            },
+            Policies {
+                partial: Policy::Skip,
+                ..flag
+            },
+            Policies {
+                slippage: Policy::Skip,
+                ..flag
+            },
+        ] {
+            assert_eq!(skip.flags(&odd), None);
+        }
+        let plain = cds("1..90", &[]);
+        let skip_all = Policies {
+            pseudo: Policy::Skip,
+            partial: Policy::Skip,
+            slippage: Policy::Skip,
+        };
+        assert_eq!(skip_all.flags(&plain).unwrap(), "");
+        assert!(is_pseudo(&cds("1..90", &["pseudo"])));
+    }
+
+    #[test]
+    fn parses_policies() {
+        assert_eq!("flag".parse::<Policy>(), Ok(Policy::Flag));
+        assert!("drop".parse::<Policy>().is_err());
+    }
+}
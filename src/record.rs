@@ -0,0 +1,614 @@
+use crate::lines;
+use crate::location::Location;
+use crate::translate::{amino_acid, translate_cds, CodonTable};
+use std::fmt;
+use std::io;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Where the protein sequence written for a CDS came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProteinSource {
+    /// The `/translation` qualifier deposited with the record.
+    Annotated,
+    /// Our own translation of the nucleotide sequence.
+    Translated,
+}
+
+impl fmt::Display for ProteinSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProteinSource::Annotated => write!(f, "annotated"),
+            ProteinSource::Translated => write!(f, "translated"),
+        }
+    }
+}
+
+/// A single annotated feature from the feature table.
+#[derive(Default, Clone, Debug)]
+pub struct Feature {
+    pub kind: String,
+    pub location: Location,
+    pub qualifiers: Vec<(String, String)>,
+}
+
+impl Feature {
+    /// First value of the qualifier `key`, without the leading `/`.
+    pub fn qualifier(&self, key: &str) -> Option<&str> {
+        self.qualifiers
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn has_qualifier(&self, key: &str) -> bool {
+        self.qualifiers.iter().any(|(k, _)| k == key)
+    }
+    pub fn locus_tag(&self) -> &str {
+        self.qualifier("locus_tag").unwrap_or_default()
+    }
+    /// `/codon_start` as a 0-based frame offset.
+    pub fn codon_offset(&self) -> usize {
+        self.qualifier("codon_start")
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .map(|v| v.clamp(1, 3) - 1)
+            .unwrap_or(0)
+    }
+    /// `/transl_table`, defaulting to the standard code, or 0 when it is
+    /// not a number.
+    pub fn transl_table(&self) -> u8 {
+        match self.qualifier("transl_table") {
+            Some(v) => v.trim().parse::<u8>().unwrap_or(0),
+            None => 1,
+        }
+    }
+    /// Translate the feature from the record sequence, honouring
+    /// `/codon_start`, `/transl_table` and `/transl_except`.
+    pub fn translate(&self, sequence: &[u8]) -> io::Result<String> {
+        let coding = self.location.extract(sequence)?;
+        let coding = coding.get(self.codon_offset()..).unwrap_or_default();
+        let table = CodonTable::new(self.transl_table()).ok_or_else(|| {
+            invalid(format!(
+                "Unknown genetic code /transl_table={} for the {} at {}",
+                self.qualifier("transl_table").unwrap_or_default(),
+                self.kind,
+                self.location
+            ))
+        })?;
+        let mut protein =
+            translate_cds(coding, &table, !self.location.five_prime_partial()).into_bytes();
+        for (key, value) in &self.qualifiers {
+            if key == "transl_except" {
+                self.apply_exception(value, &mut protein)?;
+            }
+        }
+        Ok(String::from_utf8_lossy(&protein).into_owned())
+    }
+    /// Put the residue of a `/transl_except` such as
+    /// `(pos:complement(1002..1004),aa:Sec)` in place of the codon's. A
+    /// `TERM` on an incomplete last codon adds the stop.
+    fn apply_exception(&self, value: &str, protein: &mut Vec<u8>) -> io::Result<()> {
+        let err = || {
+            invalid(format!(
+                "Could not parse /transl_except={} of the {} at {}",
+                value, self.kind, self.location
+            ))
+        };
+        let inner = value.trim().trim_start_matches('(').trim_end_matches(')');
+        let (position, residue) = inner.rsplit_once(",aa:").ok_or_else(err)?;
+        let location = Location::parse(position.trim().strip_prefix("pos:").ok_or_else(err)?)?;
+        let residue = amino_acid(residue.trim()).ok_or_else(err)?;
+        let first = match location.segments.first() {
+            Some(segment) if segment.strand == -1 => segment.end - 1,
+            Some(segment) => segment.start,
+            None => return Err(err()),
+        };
+        let codon = self
+            .location
+            .spliced_index(first)
+            .and_then(|index| index.checked_sub(self.codon_offset()))
+            .map(|index| index / 3)
+            .filter(|&codon| codon <= protein.len())
+            .ok_or_else(|| {
+                invalid(format!(
+                    "/transl_except={} lies outside the CDS at {}",
+                    value, self.location
+                ))
+            })?;
+        match protein.get_mut(codon) {
+            Some(aa) => *aa = residue,
+            None => protein.push(residue),
+        }
+        Ok(())
+    }
+    /// The `/translation` qualifier when present and `prefer_annotated` is
+    /// set, otherwise our own translation.
+    pub fn protein(
+        &self,
+        sequence: &[u8],
+        prefer_annotated: bool,
+    ) -> io::Result<(String, ProteinSource)> {
+        match self.qualifier("translation") {
+            Some(annotated) if prefer_annotated => {
+                Ok((annotated.to_string(), ProteinSource::Annotated))
+            }
+            _ => Ok((self.translate(sequence)?, ProteinSource::Translated)),
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Record {
+    pub(crate) id: String,
+    pub(crate) length: u32,
+    pub(crate) circular: bool,
+    /// Molecule type as written, e.g. `DNA`, `mRNA` or `genomic DNA`.
+    pub(crate) molecule: String,
+    /// GenBank division, e.g. `BCT`, from the `LOCUS` line.
+    pub(crate) division: String,
+    /// Date of the last change, e.g. `09-MAR-2022`.
+    pub(crate) date: String,
+    pub(crate) accession: String,
+    pub(crate) version: String,
+    pub(crate) definition: String,
+    pub(crate) organism: String,
+    /// Lineage of the organism, `;` separated.
+    pub(crate) taxonomy: String,
+    pub(crate) sequence: Vec<u8>,
+    pub(crate) features: Vec<Feature>,
+}
+
+impl Record {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Record::default()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty() && self.length == 0
+    }
+    pub fn check(&self) -> Result<(), &str> {
+        if self.id().is_empty() {
+            return Err("Expecting id for Gbk record.");
+        }
+        Ok(())
+    }
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+    /// True for records whose header declares a circular topology.
+    pub fn is_circular(&self) -> bool {
+        self.circular
+    }
+    /// Molecule type from the LOCUS or ID line, empty when not given.
+    pub fn molecule(&self) -> &str {
+        &self.molecule
+    }
+    /// GenBank division from the LOCUS line, empty when not given.
+    pub fn division(&self) -> &str {
+        &self.division
+    }
+    /// Date from the LOCUS line or the last EMBL DT line, empty when not
+    /// given.
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+    /// Primary accession, falling back to the record id.
+    pub fn accession(&self) -> &str {
+        if self.accession.is_empty() {
+            &self.id
+        } else {
+            &self.accession
+        }
+    }
+    /// `accession.version`, falling back to the accession.
+    pub fn version(&self) -> &str {
+        if self.version.is_empty() {
+            self.accession()
+        } else {
+            &self.version
+        }
+    }
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+    /// Source organism from the header, or the `/organism` of the source feature.
+    pub fn organism(&self) -> &str {
+        if !self.organism.is_empty() {
+            return &self.organism;
+        }
+        self.source()
+            .and_then(|source| source.qualifier("organism"))
+            .unwrap_or_default()
+    }
+    /// Taxa of the organism's lineage, from its kingdom down.
+    pub fn taxonomy(&self) -> impl Iterator<Item = &str> {
+        self.taxonomy
+            .split(';')
+            .map(str::trim)
+            .filter(|taxon| !taxon.is_empty())
+    }
+    /// The `source` feature describing the whole record, if any.
+    pub fn source(&self) -> Option<&Feature> {
+        self.features.iter().find(|f| f.kind == "source")
+    }
+    pub fn sequence(&self) -> &[u8] {
+        &self.sequence
+    }
+    pub fn features(&self) -> &[Feature] {
+        &self.features
+    }
+    /// Features whose key is `CDS`, in file order.
+    pub fn cds(&self) -> impl Iterator<Item = &Feature> {
+        self.features.iter().filter(|f| f.kind == "CDS")
+    }
+    /// Bases `start..end`, 0-based and end-exclusive, as a linear record of
+    /// their own named `ID_START-END`, with every feature overlapping them
+    /// shifted into the slice. Features running past either end are cut and
+    /// marked partial with `<` or `>`; their `/translation` is dropped and
+    /// `/codon_start` moved to the first whole codon left. The slice keeps
+    /// the accession, division and date of the record but has no version.
+    pub fn slice(&self, start: usize, end: usize) -> io::Result<Record> {
+        if start >= end || end > self.sequence.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}:{}-{} is outside the {} bases of the record",
+                    self.id,
+                    start + 1,
+                    end,
+                    self.sequence.len()
+                ),
+            ));
+        }
+        let mut features = Vec::new();
+        for feature in &self.features {
+            let (mut location, trimmed) = match feature.location.clip(start, end) {
+                Some(clipped) => clipped,
+                None => continue,
+            };
+            let mut qualifiers = feature.qualifiers.clone();
+            if feature.kind == "source" {
+                // the slice is the whole of its own source
+                for segment in location.segments.iter_mut() {
+                    segment.partial_start = false;
+                    segment.partial_end = false;
+                }
+            } else if location.len() < feature.location.len() {
+                qualifiers.retain(|(key, _)| key != "translation");
+                let offset = (feature.codon_offset() + 3 - trimmed % 3) % 3;
+                let codon_start = (offset + 1).to_string();
+                match qualifiers.iter_mut().find(|(key, _)| key == "codon_start") {
+                    Some((_, value)) => *value = codon_start,
+                    None if feature.kind == "CDS" && offset > 0 => {
+                        qualifiers.push(("codon_start".to_string(), codon_start))
+                    }
+                    None => {}
+                }
+            }
+            features.push(Feature {
+                kind: feature.kind.clone(),
+                location,
+                qualifiers,
+            });
+        }
+        Ok(Record {
+            id: format!("{}_{}-{}", self.id, start + 1, end),
+            length: (end - start) as u32,
+            circular: false,
+            molecule: self.molecule.clone(),
+            division: self.division.clone(),
+            date: self.date.clone(),
+            accession: self.accession.clone(),
+            // a new sequence, so not the parent's version
+            version: String::new(),
+            definition: self.definition.clone(),
+            organism: self.organism.clone(),
+            taxonomy: self.taxonomy.clone(),
+            sequence: self.sequence[start..end].to_vec(),
+            features,
+        })
+    }
+    pub(crate) fn rec_clear(&mut self) {
+        self.id.clear();
+        self.length = 0;
+        self.circular = false;
+        self.molecule.clear();
+        self.division.clear();
+        self.date.clear();
+        self.accession.clear();
+        self.version.clear();
+        self.definition.clear();
+        self.organism.clear();
+        self.taxonomy.clear();
+        self.sequence.clear();
+        self.features.clear();
+    }
+}
+
+/// Builds features from feature table lines with the 5 column prefix
+/// (`     ` in GenBank, `FT   ` in EMBL) already removed.
+#[derive(Default)]
+pub(crate) struct FeatureTable {
+    features: Vec<Feature>,
+    kind: String,
+    location: String,
+    /// Qualifier values as written, continuation lines already joined.
+    qualifiers: Vec<(String, String)>,
+    /// Whether the last qualifier value has an unmatched `"`.
+    open_quote: bool,
+}
+
+impl FeatureTable {
+    pub(crate) fn push_line(&mut self, line: &str) -> io::Result<()> {
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(());
+        }
+        if !line.starts_with(' ') {
+            self.finish_feature()?;
+            let mut fields = line.splitn(2, char::is_whitespace);
+            self.kind = fields.next().unwrap_or_default().to_string();
+            self.location = fields.next().unwrap_or_default().trim().to_string();
+        } else if self.kind.is_empty() {
+            return Ok(());
+        } else {
+            let text = line.trim_start();
+            let quotes = text.bytes().filter(|&b| b == b'"').count() % 2 == 1;
+            if let (true, Some(stripped)) = (!self.open_quote, text.strip_prefix('/')) {
+                let (key, value) = match stripped.split_once('=') {
+                    Some((k, v)) => (k, v),
+                    None => (stripped, ""),
+                };
+                self.qualifiers.push((key.to_string(), value.to_string()));
+                self.open_quote = quotes;
+            } else if let Some((key, value)) = self.qualifiers.last_mut() {
+                if key != "translation" {
+                    value.push(' ');
+                }
+                value.push_str(text);
+                self.open_quote ^= quotes;
+            } else {
+                self.location.push_str(text);
+            }
+        }
+        Ok(())
+    }
+    fn finish_feature(&mut self) -> io::Result<()> {
+        self.open_quote = false;
+        if self.kind.is_empty() {
+            return Ok(());
+        }
+        let location = Location::parse(&self.location)?;
+        let qualifiers = self
+            .qualifiers
+            .drain(..)
+            .map(|(key, raw)| {
+                let value = match raw.strip_prefix('"') {
+                    Some(v) => v.strip_suffix('"').unwrap_or(v).replace("\"\"", "\""),
+                    None => raw,
+                };
+                (key, value)
+            })
+            .collect();
+        self.features.push(Feature {
+            kind: std::mem::take(&mut self.kind),
+            location,
+            qualifiers,
+        });
+        self.location.clear();
+        Ok(())
+    }
+    pub(crate) fn finish(mut self) -> io::Result<Vec<Feature>> {
+        self.finish_feature()?;
+        Ok(self.features)
+    }
+}
+
+/// Qualifiers whose values are written without quotes.
+const UNQUOTED: &[&str] = &[
+    "anticodon",
+    "citation",
+    "codon_start",
+    "compare",
+    "direction",
+    "estimated_length",
+    "mod_base",
+    "number",
+    "rpt_type",
+    "rpt_unit_range",
+    "tag_peptide",
+    "transl_except",
+    "transl_table",
+];
+
+/// Letters of location and qualifier text per feature table line.
+const FEATURE_WIDTH: usize = 58;
+
+/// Write `features` as feature table lines starting with `prefix`
+/// (`     ` in GenBank, `FT   ` in EMBL).
+pub(crate) fn write_features<W: io::Write>(
+    out: &mut W,
+    prefix: &str,
+    features: &[Feature],
+) -> io::Result<()> {
+    let indent = " ".repeat(16);
+    for feature in features {
+        let location = feature.location.to_string();
+        for (i, line) in lines::wrap(&location, FEATURE_WIDTH, Some(b','))
+            .iter()
+            .enumerate()
+        {
+            if i == 0 {
+                writeln!(out, "{}{:<16}{}", prefix, feature.kind, line)?;
+            } else {
+                writeln!(out, "{}{}{}", prefix, indent, line)?;
+            }
+        }
+        for (key, value) in &feature.qualifiers {
+            let text = if value.is_empty() {
+                format!("/{}", key)
+            } else if UNQUOTED.contains(&key.as_str()) {
+                format!("/{}={}", key, value)
+            } else {
+                format!("/{}=\"{}\"", key, value.replace('"', "\"\""))
+            };
+            let separator = if key == "translation" {
+                None
+            } else {
+                Some(b' ')
+            };
+            for line in lines::wrap(&text, FEATURE_WIDTH, separator) {
+                writeln!(out, "{}{}{}", prefix, indent, line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cds(location: &str, qualifiers: &[(&str, &str)]) -> Feature {
+        Feature {
+            kind: "CDS".to_string(),
+            location: Location::parse(location).unwrap(),
+            qualifiers: qualifiers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    /// `ATG AAA TGA CCC TAA` on the plus strand, and the same CDS on the
+    /// minus strand after it.
+    const SEQUENCE: &[u8] = b"ATGAAATGACCCTAAggTTAGGGTCATTTCAT";
+
+    #[test]
+    fn translates_with_the_table_asked_for() {
+        let standard = cds("1..15", &[]);
+        assert_eq!(standard.translate(SEQUENCE).unwrap(), "MK*P*");
+        let mitochondrial = cds("1..15", &[("transl_table", "5")]);
+        assert_eq!(mitochondrial.translate(SEQUENCE).unwrap(), "MKWP*");
+        let minus = cds("complement(18..32)", &[("transl_table", "11")]);
+        assert_eq!(minus.translate(SEQUENCE).unwrap(), "MK*P*");
+    }
+
+    #[test]
+    fn applies_transl_except_on_either_strand() {
+        let plus = cds("1..15", &[("transl_except", "(pos:7..9,aa:Sec)")]);
+        assert_eq!(plus.translate(SEQUENCE).unwrap(), "MKUP*");
+        let minus = cds(
+            "complement(18..32)",
+            &[("transl_except", "(pos:complement(24..26),aa:Sec)")],
+        );
+        assert_eq!(minus.translate(SEQUENCE).unwrap(), "MKUP*");
+        let framed = cds(
+            "join(1..3,7..15)",
+            &[("transl_except", "(pos:7..9,aa:Pyl)")],
+        );
+        assert_eq!(framed.translate(SEQUENCE).unwrap(), "MOP*");
+    }
+
+    #[test]
+    fn term_completes_a_truncated_stop() {
+        let truncated = cds("1..14", &[("transl_except", "(pos:13..14,aa:TERM)")]);
+        assert_eq!(truncated.translate(SEQUENCE).unwrap(), "MK*P*");
+    }
+
+    #[test]
+    fn rejects_unknown_tables_and_bad_exceptions() {
+        let error = |feature: Feature| feature.translate(SEQUENCE).unwrap_err().to_string();
+        assert_eq!(
+            error(cds("1..15", &[("transl_table", "7")])),
+            "Unknown genetic code /transl_table=7 for the CDS at 1..15"
+        );
+        assert_eq!(
+            error(cds("1..15", &[("transl_except", "(pos:7..9,aa:Foo)")])),
+            "Could not parse /transl_except=(pos:7..9,aa:Foo) of the CDS at 1..15"
+        );
+        assert_eq!(
+            error(cds("1..15", &[("transl_except", "(pos:20..22,aa:Sec)")])),
+            "/transl_except=(pos:20..22,aa:Sec) lies outside the CDS at 1..15"
+        );
+    }
+
+    #[test]
+    fn prefers_the_annotated_translation_only_when_asked() {
+        let annotated = cds("1..15", &[("translation", "MKUP")]);
+        assert_eq!(
+            annotated.protein(SEQUENCE, true).unwrap(),
+            ("MKUP".to_string(), ProteinSource::Annotated)
+        );
+        assert_eq!(
+            annotated.protein(SEQUENCE, false).unwrap(),
+            ("MK*P*".to_string(), ProteinSource::Translated)
+        );
+        let bare = cds("1..15", &[]);
+        assert_eq!(
+            bare.protein(SEQUENCE, true).unwrap().1,
+            ProteinSource::Translated
+        );
+        assert_eq!(ProteinSource::Annotated.to_string(), "annotated");
+    }
+
+    fn sliceable(location: &str, codon_start: &str) -> Record {
+        Record {
+            id: "T1".to_string(),
+            version: "T1.3".to_string(),
+            division: "BCT".to_string(),
+            date: "09-MAR-2022".to_string(),
+            circular: true,
+            sequence: b"ttgcatggcaaagcccgatgcttaccgcat".to_vec(),
+            features: vec![cds(
+                location,
+                &[("codon_start", codon_start), ("translation", "MX")],
+            )],
+            ..Record::default()
+        }
+    }
+
+    #[test]
+    fn slice_keeps_the_reading_frame_of_a_complement_partial_cds() {
+        // 8 bases cut from the 5' end of a CDS read from its second base
+        let whole = sliceable("complement(4..>28)", "2");
+        let sliced = whole.slice(0, 20).unwrap();
+        let cds = &sliced.features()[0];
+        assert_eq!(cds.location.to_string(), "complement(4..>20)");
+        assert_eq!(cds.qualifier("codon_start"), Some("3"));
+        assert_eq!(cds.qualifier("translation"), None);
+        let protein = whole.features()[0].translate(whole.sequence()).unwrap();
+        let rest = cds.translate(sliced.sequence()).unwrap();
+        assert_eq!(rest, protein[3..]);
+    }
+
+    #[test]
+    fn slice_leaves_the_frame_of_a_cds_cut_at_its_3_prime_end() {
+        let whole = sliceable("complement(4..27)", "1");
+        let sliced = whole.slice(9, 30).unwrap();
+        let cds = &sliced.features()[0];
+        assert_eq!(cds.location.to_string(), "complement(<1..18)");
+        assert_eq!(cds.qualifier("codon_start"), Some("1"));
+        assert_eq!(sliced.id(), "T1_10-30");
+    }
+
+    #[test]
+    fn slice_is_a_new_linear_record_of_the_same_division() {
+        let sliced = sliceable("4..27", "1").slice(9, 30).unwrap();
+        assert!(!sliced.is_circular());
+        assert_eq!(sliced.length(), 21);
+        // no version of its own, so named by its id
+        assert_eq!(sliced.version(), "T1_10-30");
+        assert_eq!(sliced.division(), "BCT");
+        assert_eq!(sliced.date(), "09-MAR-2022");
+        let error = sliceable("4..27", "1").slice(9, 31).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "T1:10-31 is outside the 30 bases of the record"
+        );
+    }
+}
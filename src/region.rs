@@ -0,0 +1,90 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A record name with an optional 1-based, inclusive range, written
+/// `NC_000913.3` or `NC_000913.3:1,000-2,000` as for `samtools faidx`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub name: String,
+    /// 1-based first and last base.
+    pub range: Option<(u64, u64)>,
+}
+
+impl Region {
+    /// The range as 0-based, end-exclusive coordinates.
+    pub fn span(&self) -> Option<(u64, u64)> {
+        self.range.map(|(start, end)| (start - 1, end))
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.range {
+            Some((start, end)) => write!(f, "{}:{}-{}", self.name, start, end),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, range) = match s.rsplit_once(':') {
+            Some((name, range)) => (name, range),
+            None => {
+                return Ok(Region {
+                    name: s.to_string(),
+                    range: None,
+                })
+            }
+        };
+        let position = |text: &str| text.replace(',', "").trim().parse::<u64>().ok();
+        let (start, end) = range
+            .split_once('-')
+            .and_then(|(start, end)| Some((position(start)?, position(end)?)))
+            .ok_or_else(|| format!("invalid region {:?}, expected NAME:START-END", s))?;
+        if start == 0 || start > end {
+            return Err(format!(
+                "invalid region {:?}, expected 1 <= START <= END",
+                s
+            ));
+        }
+        Ok(Region {
+            name: name.to_string(),
+            range: Some((start, end)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_with_optional_ranges() {
+        let region: Region = "NC_000913.3:1,000-2,000".parse().unwrap();
+        assert_eq!(region.name, "NC_000913.3");
+        assert_eq!(region.range, Some((1000, 2000)));
+        assert_eq!(region.span(), Some((999, 2000)));
+        assert_eq!(region.to_string(), "NC_000913.3:1000-2000");
+        let whole: Region = "NC_000913.3".parse().unwrap();
+        assert_eq!((whole.range, whole.span()), (None, None));
+        assert_eq!(whole.to_string(), "NC_000913.3");
+        let single: Region = "chr:5-5".parse().unwrap();
+        assert_eq!(single.span(), Some((4, 5)));
+    }
+
+    #[test]
+    fn rejects_empty_and_reversed_ranges() {
+        for (text, error) in [
+            ("NC_1:10", "expected NAME:START-END"),
+            ("NC_1:a-b", "expected NAME:START-END"),
+            ("NC_1:0-10", "expected 1 <= START <= END"),
+            ("NC_1:20-10", "expected 1 <= START <= END"),
+        ] {
+            let message = text.parse::<Region>().unwrap_err();
+            assert!(message.ends_with(error), "{}", message);
+        }
+    }
+}
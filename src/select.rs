@@ -0,0 +1,264 @@
+use crate::record::Record;
+use anyhow::Context;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Shape of a record's molecule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    Linear,
+    Circular,
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Topology::Linear => "linear",
+            Topology::Circular => "circular",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(Topology::Linear),
+            "circular" => Ok(Topology::Circular),
+            _ => Err(format!(
+                "unknown topology {:?}, expected linear or circular",
+                s
+            )),
+        }
+    }
+}
+
+/// Record names listed one per line, as ids, accessions or
+/// accession.versions. Anything after the first word of a line is ignored,
+/// as are blank lines and lines starting with `#`.
+#[derive(Clone, Debug, Default)]
+pub struct AccessionList {
+    names: HashSet<String>,
+}
+
+impl AccessionList {
+    pub fn read<R: BufRead>(reader: R) -> io::Result<AccessionList> {
+        let mut names = HashSet::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.split_whitespace().next() {
+                names.insert(name.to_string());
+            }
+        }
+        Ok(AccessionList { names })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<AccessionList> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        AccessionList::read(io::BufReader::new(file))
+            .with_context(|| format!("Failed to read {:?}", path))
+    }
+
+    /// True when the record's id, accession or accession.version is listed.
+    pub fn contains(&self, record: &Record) -> bool {
+        [record.id(), record.accession(), record.version()]
+            .iter()
+            .any(|name| self.names.contains(*name))
+    }
+}
+
+/// Conditions a record has to meet to be read any further; those left
+/// unset accept every record.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// Only records in this list.
+    pub include: Option<AccessionList>,
+    /// No records in this list.
+    pub exclude: Option<AccessionList>,
+    /// Fewest bases, as declared on the LOCUS or ID line.
+    pub min_length: Option<u32>,
+    pub max_length: Option<u32>,
+    /// Searched for in the DEFINITION or DE lines.
+    pub definition: Option<Regex>,
+    /// A taxon of the lineage, or the organism, ignoring case.
+    pub taxon: Option<String>,
+    pub topology: Option<Topology>,
+    /// Molecule type, ignoring case; `DNA` also matches `genomic DNA`.
+    pub molecule: Option<String>,
+}
+
+impl Selection {
+    pub fn accepts(&self, record: &Record) -> bool {
+        if let Some(include) = &self.include {
+            if !include.contains(record) {
+                return false;
+            }
+        }
+        if let Some(exclude) = &self.exclude {
+            if exclude.contains(record) {
+                return false;
+            }
+        }
+        let length = record.length();
+        if self.min_length.is_some_and(|min| length < min)
+            || self.max_length.is_some_and(|max| length > max)
+        {
+            return false;
+        }
+        if let Some(definition) = &self.definition {
+            if !definition.is_match(record.definition()) {
+                return false;
+            }
+        }
+        if let Some(taxon) = &self.taxon {
+            let found = record
+                .taxonomy()
+                .chain(std::iter::once(record.organism()))
+                .any(|name| name.eq_ignore_ascii_case(taxon));
+            if !found {
+                return false;
+            }
+        }
+        if let Some(topology) = self.topology {
+            if record.is_circular() != (topology == Topology::Circular) {
+                return false;
+            }
+        }
+        if let Some(molecule) = &self.molecule {
+            let written = record.molecule();
+            let last = written.rsplit(' ').next().unwrap_or_default();
+            if !written.eq_ignore_ascii_case(molecule) && !last.eq_ignore_ascii_case(molecule) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, version: &str, length: u32) -> Record {
+        Record {
+            id: id.to_string(),
+            accession: id.to_string(),
+            version: version.to_string(),
+            length,
+            circular: true,
+            molecule: "genomic DNA".to_string(),
+            definition: "Escherichia coli K-12 chromosome, complete genome".to_string(),
+            organism: "Escherichia coli".to_string(),
+            taxonomy: "Bacteria; Pseudomonadota; Enterobacterales; Enterobacteriaceae".to_string(),
+            ..Record::default()
+        }
+    }
+
+    fn list(text: &str) -> AccessionList {
+        AccessionList::read(text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn reads_the_first_word_of_each_line() {
+        let names = list("# wanted\nNC_1.2 chromosome\n\n  NC_3\t plasmid\n");
+        assert!(names.contains(&record("NC_1", "NC_1.2", 100)));
+        assert!(!names.contains(&record("NC_1", "NC_1.1", 100)));
+        assert!(names.contains(&record("NC_3", "NC_3.1", 100)));
+        assert!(!names.contains(&record("wanted", "", 100)));
+    }
+
+    #[test]
+    fn includes_and_excludes_listed_records() {
+        let (first, second) = (record("NC_1", "NC_1.1", 100), record("NC_2", "NC_2.1", 100));
+        let include = Selection {
+            include: Some(list("NC_1\n")),
+            ..Selection::default()
+        };
+        assert!(include.accepts(&first) && !include.accepts(&second));
+        let exclude = Selection {
+            exclude: Some(list("NC_1.1\n")),
+            ..Selection::default()
+        };
+        assert!(!exclude.accepts(&first) && exclude.accepts(&second));
+        let both = Selection {
+            include: Some(list("NC_1\nNC_2\n")),
+            exclude: Some(list("NC_2\n")),
+            ..Selection::default()
+        };
+        assert!(both.accepts(&first) && !both.accepts(&second));
+        assert!(Selection::default().accepts(&second));
+    }
+
+    #[test]
+    fn selects_by_length_definition_and_taxon() {
+        let record = record("NC_1", "NC_1.1", 100);
+        for (min, max, accepted) in [
+            (Some(100), None, true),
+            (Some(101), None, false),
+            (None, Some(100), true),
+            (None, Some(99), false),
+        ] {
+            let selection = Selection {
+                min_length: min,
+                max_length: max,
+                ..Selection::default()
+            };
+            assert_eq!(selection.accepts(&record), accepted, "{:?}-{:?}", min, max);
+        }
+        for (pattern, accepted) in [
+            ("complete genome", true),
+            ("(?i)^escherichia", true),
+            ("plasmid", false),
+        ] {
+            let selection = Selection {
+                definition: Some(Regex::new(pattern).unwrap()),
+                ..Selection::default()
+            };
+            assert_eq!(selection.accepts(&record), accepted, "{}", pattern);
+        }
+        for (taxon, accepted) in [
+            ("enterobacterales", true),
+            ("Escherichia coli", true),
+            ("Enterobacter", false),
+            ("Firmicutes", false),
+        ] {
+            let selection = Selection {
+                taxon: Some(taxon.to_string()),
+                ..Selection::default()
+            };
+            assert_eq!(selection.accepts(&record), accepted, "{}", taxon);
+        }
+    }
+
+    #[test]
+    fn selects_by_topology_and_molecule() {
+        let record = record("NC_1", "NC_1.1", 100);
+        assert!("Circular".parse::<Topology>().is_ok());
+        assert!("round".parse::<Topology>().is_err());
+        for (topology, accepted) in [(Topology::Circular, true), (Topology::Linear, false)] {
+            let selection = Selection {
+                topology: Some(topology),
+                ..Selection::default()
+            };
+            assert_eq!(selection.accepts(&record), accepted, "{}", topology);
+        }
+        for (molecule, accepted) in [("dna", true), ("genomic DNA", true), ("mRNA", false)] {
+            let selection = Selection {
+                molecule: Some(molecule.to_string()),
+                ..Selection::default()
+            };
+            assert_eq!(selection.accepts(&record), accepted, "{}", molecule);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::compression::{BgzfWriter, Compression};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// A file written under a hidden temporary name in the same directory and
+/// renamed into place by `commit`, so an interrupted run never leaves an
+/// output that looks finished. Dropping it uncommitted removes the
+/// temporary file. Existing targets that are not regular files, such as
+/// `/dev/stdout`, `/dev/null` or a named pipe, are written directly.
+pub struct AtomicFile {
+    file: Option<io::BufWriter<fs::File>>,
+    /// `None` when writing straight to `path`.
+    temp: Option<PathBuf>,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if fs::symlink_metadata(&path).is_ok_and(|meta| !meta.file_type().is_file()) {
+            let file = fs::File::create(&path)?;
+            return Ok(AtomicFile {
+                file: Some(io::BufWriter::new(file)),
+                temp: None,
+                path,
+            });
+        }
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "output path has no file name")
+        })?;
+        let temp = path.with_file_name(format!(".{}.tmp{}", name.to_string_lossy(), process::id()));
+        let file = fs::File::create(&temp)?;
+        Ok(AtomicFile {
+            file: Some(io::BufWriter::new(file)),
+            temp: Some(temp),
+            path,
+        })
+    }
+
+    /// Add to the end of the file at `path`, writing it directly.
+    pub fn append<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        Ok(AtomicFile {
+            file: Some(io::BufWriter::new(file)),
+            temp: None,
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flush everything to disk and move the file to its final name.
+    pub fn commit(mut self) -> io::Result<()> {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        file.flush()?;
+        if let Some(temp) = &self.temp {
+            file.get_ref().sync_all()?;
+            fs::rename(temp, &self.path)?;
+        }
+        self.file = None;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.file.as_mut() {
+            Some(file) => file.write(buf),
+            None => Err(io::Error::other("write to a committed file")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if let (Some(_), Some(temp)) = (self.file.take(), &self.temp) {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+/// Where a sink ends up.
+pub enum Target {
+    Stdout(io::BufWriter<io::Stdout>),
+    File(AtomicFile),
+}
+
+impl Write for Target {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Target::Stdout(out) => out.write(buf),
+            Target::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Target::Stdout(out) => out.flush(),
+            Target::File(file) => file.flush(),
+        }
+    }
+}
+
+enum Encoder {
+    Plain(Target),
+    Gzip(flate2::write::GzEncoder<Target>),
+    Bgzf(BgzfWriter<Target>),
+    Bzip2(bzip2::write::BzEncoder<Target>),
+    Xz(xz2::write::XzEncoder<Target>),
+    Zstd(zstd::Encoder<'static, Target>),
+}
+
+/// Buffered, optionally compressed output to stdout or to a file. Call
+/// `finish` once everything is written; file outputs only appear under
+/// their name then.
+pub struct Sink {
+    encoder: Encoder,
+}
+
+impl Sink {
+    pub fn stdout(compression: Compression) -> io::Result<Self> {
+        Sink::new(
+            Target::Stdout(io::BufWriter::new(io::stdout())),
+            compression,
+        )
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P, compression: Compression) -> io::Result<Self> {
+        Sink::new(Target::File(AtomicFile::create(path)?), compression)
+    }
+
+    /// As `create`, but adds to the end of an existing file, starting a new
+    /// compressed stream.
+    pub fn append<P: AsRef<Path>>(path: P, compression: Compression) -> io::Result<Self> {
+        Sink::new(Target::File(AtomicFile::append(path)?), compression)
+    }
+
+    fn new(target: Target, compression: Compression) -> io::Result<Self> {
+        let level = flate2::Compression::default();
+        let encoder = match compression {
+            Compression::None => Encoder::Plain(target),
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(target, level)),
+            Compression::Bgzf => Encoder::Bgzf(BgzfWriter::new(target)),
+            Compression::Bzip2 => Encoder::Bzip2(bzip2::write::BzEncoder::new(
+                target,
+                bzip2::Compression::default(),
+            )),
+            Compression::Xz => Encoder::Xz(xz2::write::XzEncoder::new(target, 6)),
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(target, 0)?),
+        };
+        Ok(Sink { encoder })
+    }
+
+    /// Finish any compressed stream, flush, and rename a file output into
+    /// place.
+    pub fn finish(self) -> io::Result<()> {
+        let target = match self.encoder {
+            Encoder::Plain(target) => target,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Bgzf(encoder) => encoder.finish()?,
+            Encoder::Bzip2(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        match target {
+            Target::Stdout(mut out) => out.flush(),
+            Target::File(file) => file.commit(),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.encoder {
+            Encoder::Plain(w) => w.write(buf),
+            Encoder::Gzip(w) => w.write(buf),
+            Encoder::Bgzf(w) => w.write(buf),
+            Encoder::Bzip2(w) => w.write(buf),
+            Encoder::Xz(w) => w.write(buf),
+            Encoder::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(w) => w.flush(),
+            Encoder::Gzip(w) => w.flush(),
+            Encoder::Bgzf(w) => w.flush(),
+            Encoder::Bzip2(w) => w.flush(),
+            Encoder::Xz(w) => w.flush(),
+            Encoder::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression;
+    use std::io::Read;
+
+    /// An empty directory of its own for each test.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("annots-sink-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> Vec<u8> {
+        let mut data = Vec::new();
+        compression::open(path)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        data
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn reads_back_what_it_compressed() {
+        let dir = scratch("round-trip");
+        for compression in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Bgzf,
+            Compression::Bzip2,
+            Compression::Xz,
+            Compression::Zstd,
+        ] {
+            let path = dir.join(format!("out.fna{}", compression.extension()));
+            let mut sink = Sink::create(&path, compression).unwrap();
+            sink.write_all(b">T_1\nATG\n").unwrap();
+            sink.finish().unwrap();
+            assert_eq!(compression::sniff(&path).unwrap(), compression);
+            assert_eq!(read(&path), b">T_1\nATG\n");
+            fs::remove_file(&path).unwrap();
+        }
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_appears_once_finished() {
+        let dir = scratch("atomic");
+        let path = dir.join("out.fna");
+        let mut sink = Sink::create(&path, Compression::None).unwrap();
+        sink.write_all(b">T_1\nATG\n").unwrap();
+        sink.flush().unwrap();
+        let temp = format!(".out.fna.tmp{}", process::id());
+        assert_eq!(names(&dir), vec![temp.clone()]);
+        sink.finish().unwrap();
+        assert_eq!(names(&dir), vec!["out.fna"]);
+
+        let mut sink = Sink::create(&path, Compression::Gzip).unwrap();
+        sink.write_all(b">T_2\nTAA\n").unwrap();
+        assert_eq!(names(&dir), vec![temp, "out.fna".to_string()]);
+        drop(sink);
+        assert_eq!(names(&dir), vec!["out.fna"]);
+        assert_eq!(fs::read(&path).unwrap(), b">T_1\nATG\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn appends_new_streams() {
+        let dir = scratch("append");
+        let path = dir.join("out.fna.gz");
+        for record in [&b">T_1\nATG\n"[..], b">T_2\nTAA\n"] {
+            let mut sink = Sink::append(&path, Compression::Gzip).unwrap();
+            sink.write_all(record).unwrap();
+            sink.finish().unwrap();
+        }
+        assert_eq!(read(&path), b">T_1\nATG\n>T_2\nTAA\n");
+        assert_eq!(names(&dir), vec!["out.fna.gz"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_straight_to_special_files() {
+        let mut file = AtomicFile::create("/dev/null").unwrap();
+        file.write_all(b">T_1\nATG\n").unwrap();
+        file.commit().unwrap();
+        assert!(!fs::metadata("/dev/null").unwrap().file_type().is_file());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::policy::is_pseudo;
+use crate::record::Record;
+use std::fmt;
+
+/// Column names of the stats TSV.
+pub const HEADER: &str =
+    "record\taccession\tlength\ttopology\tgc_percent\tfeatures\tgenes\tcds\tpseudo\trrna\ttrna\torganism";
+
+/// Summary of one record, written as a row of the stats TSV.
+#[derive(Default, Clone, Debug)]
+pub struct RecordStats {
+    pub record: String,
+    pub accession: String,
+    pub length: usize,
+    pub circular: bool,
+    /// G and C bases as a percentage of unambiguous bases.
+    pub gc_percent: f64,
+    /// Every feature apart from `source`.
+    pub features: usize,
+    pub genes: usize,
+    pub cds: usize,
+    /// CDS marked `/pseudo` or `/pseudogene`.
+    pub pseudo: usize,
+    pub rrna: usize,
+    pub trna: usize,
+    pub organism: String,
+}
+
+impl RecordStats {
+    pub fn new(record: &Record) -> Self {
+        let (mut gc, mut acgt) = (0, 0);
+        for base in record.sequence() {
+            match base.to_ascii_uppercase() {
+                b'G' | b'C' => {
+                    gc += 1;
+                    acgt += 1
+                }
+                b'A' | b'T' | b'U' => acgt += 1,
+                _ => {}
+            }
+        }
+        let mut stats = RecordStats {
+            record: record.id().to_string(),
+            accession: record.accession().to_string(),
+            length: record.sequence().len(),
+            circular: record.is_circular(),
+            gc_percent: if acgt == 0 {
+                0.0
+            } else {
+                100.0 * gc as f64 / acgt as f64
+            },
+            organism: record.organism().to_string(),
+            ..RecordStats::default()
+        };
+        for feature in record.features() {
+            match feature.kind.as_str() {
+                "source" => continue,
+                "gene" => stats.genes += 1,
+                "CDS" => {
+                    stats.cds += 1;
+                    if is_pseudo(feature) {
+                        stats.pseudo += 1;
+                    }
+                }
+                "rRNA" => stats.rrna += 1,
+                "tRNA" => stats.trna += 1,
+                _ => {}
+            }
+            stats.features += 1;
+        }
+        stats
+    }
+}
+
+impl fmt::Display for RecordStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.record,
+            self.accession,
+            self.length,
+            if self.circular { "circular" } else { "linear" },
+            self.gc_percent,
+            self.features,
+            self.genes,
+            self.cds,
+            self.pseudo,
+            self.rrna,
+            self.trna,
+            self.organism
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+    use crate::record::Feature;
+
+    fn feature(kind: &str, qualifiers: &[(&str, &str)]) -> Feature {
+        Feature {
+            kind: kind.to_string(),
+            location: Location::parse("1..9").unwrap(),
+            qualifiers: qualifiers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn counts_features_and_gc() {
+        let record = Record {
+            id: "NC_1".to_string(),
+            circular: true,
+            organism: "Escherichia coli".to_string(),
+            sequence: b"GGCCatnn".to_vec(),
+            features: vec![
+                feature("source", &[]),
+                feature("gene", &[]),
+                feature("CDS", &[]),
+                feature("CDS", &[("pseudo", "")]),
+                feature("tRNA", &[]),
+                feature("rRNA", &[]),
+                feature("repeat_region", &[]),
+            ],
+            ..Record::default()
+        };
+        let stats = RecordStats::new(&record);
+        assert_eq!(
+            stats.to_string(),
+            "NC_1\tNC_1\t8\tcircular\t66.67\t6\t1\t2\t1\t1\t1\tEscherichia coli"
+        );
+        assert_eq!(
+            HEADER.split('\t').count(),
+            stats.to_string().split('\t').count()
+        );
+        assert_eq!(RecordStats::new(&Record::default()).gc_percent, 0.0);
+    }
+}
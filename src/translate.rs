@@ -0,0 +1,543 @@
+use std::str::FromStr;
+
+/// Amino acids and start codons for each codon in TCAG order, per NCBI
+/// translation table, as in the NCBI `gc.prt`.
+const TABLES: &[(u8, &[u8; 64], &[u8; 64])] = &[
+    (
+        1,
+        b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"---M---------------M---------------M----------------------------",
+    ),
+    (
+        2,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+        b"--------------------------------MMMM---------------M------------",
+    ),
+    (
+        3,
+        b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"----------------------------------MM---------------M------------",
+    ),
+    (
+        4,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"--MM---------------M------------MMMM---------------M------------",
+    ),
+    (
+        5,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+        b"---M----------------------------MMMM---------------M------------",
+    ),
+    (
+        6,
+        b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        9,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        b"-----------------------------------M---------------M------------",
+    ),
+    (
+        10,
+        b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        11,
+        b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"---M---------------M------------MMMM---------------M------------",
+    ),
+    (
+        12,
+        b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-------------------M---------------M----------------------------",
+    ),
+    (
+        13,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+        b"---M------------------------------MM---------------M------------",
+    ),
+    (
+        14,
+        b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        15,
+        b"FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        16,
+        b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        21,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        b"-----------------------------------M---------------M------------",
+    ),
+    (
+        22,
+        b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        23,
+        b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"--------------------------------M--M---------------M------------",
+    ),
+    (
+        24,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        b"---M---------------M---------------M---------------M------------",
+    ),
+    (
+        25,
+        b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"---M-------------------------------M---------------M------------",
+    ),
+    (
+        26,
+        b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-------------------M---------------M----------------------------",
+    ),
+    (
+        27,
+        b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        28,
+        b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        29,
+        b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        30,
+        b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        31,
+        b"FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"-----------------------------------M----------------------------",
+    ),
+    (
+        32,
+        b"FFLLSSSSYY*WCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"---M---------------M------------MMMM---------------M------------",
+    ),
+    (
+        33,
+        b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        b"---M---------------M---------------M---------------M------------",
+    ),
+];
+
+/// An NCBI genetic code, selected by `/transl_table`.
+#[derive(Clone, Copy, Debug)]
+pub struct CodonTable {
+    amino_acids: &'static [u8; 64],
+    starts: &'static [u8; 64],
+}
+
+impl CodonTable {
+    /// Look up a table by NCBI id, or `None` for an id NCBI does not define.
+    pub fn new(id: u8) -> Option<Self> {
+        TABLES
+            .iter()
+            .find(|(table, _, _)| *table == id)
+            .map(|&(_, amino_acids, starts)| CodonTable {
+                amino_acids,
+                starts,
+            })
+    }
+    /// Table indices of every concrete codon an IUPAC codon may stand for.
+    fn indices(codon: &[u8]) -> Vec<usize> {
+        codon.iter().fold(vec![0], |acc, base| {
+            let bases: &[usize] = match base.to_ascii_uppercase() {
+                b'T' | b'U' => &[0],
+                b'C' => &[1],
+                b'A' => &[2],
+                b'G' => &[3],
+                b'Y' => &[0, 1],
+                b'R' => &[2, 3],
+                b'W' => &[0, 2],
+                b'S' => &[1, 3],
+                b'K' => &[0, 3],
+                b'M' => &[1, 2],
+                b'B' => &[0, 1, 3],
+                b'D' => &[0, 2, 3],
+                b'H' => &[0, 1, 2],
+                b'V' => &[1, 2, 3],
+                b'N' => &[0, 1, 2, 3],
+                _ => &[],
+            };
+            acc.iter()
+                .flat_map(|i| bases.iter().map(move |n| i * 4 + n))
+                .collect()
+        })
+    }
+    /// Translate one codon. IUPAC codons resolve when every codon they
+    /// stand for gives the same residue (`GAY` is `D`), otherwise `X`.
+    pub fn codon(&self, codon: &[u8]) -> u8 {
+        let mut residues = Self::indices(codon)
+            .into_iter()
+            .map(|i| self.amino_acids[i]);
+        match residues.next() {
+            Some(first) if residues.all(|aa| aa == first) => first,
+            _ => b'X',
+        }
+    }
+    pub fn is_start(&self, codon: &[u8]) -> bool {
+        let indices = Self::indices(codon);
+        !indices.is_empty() && indices.iter().all(|&i| self.starts[i] == b'M')
+    }
+}
+
+impl Default for CodonTable {
+    fn default() -> Self {
+        CodonTable::new(1).expect("the standard code is table 1")
+    }
+}
+
+/// Translate a nucleotide sequence with the standard code, dropping any
+/// trailing incomplete codon.
+pub fn translate(seq: &[u8]) -> String {
+    translate_with(seq, &CodonTable::default())
+}
+
+pub fn translate_with(seq: &[u8], table: &CodonTable) -> String {
+    seq.chunks_exact(3)
+        .map(|codon| table.codon(codon) as char)
+        .collect()
+}
+
+/// Translate a coding sequence the way INSDC `/translation` values are
+/// built: an alternative start codon at a complete 5' end becomes `M`.
+pub fn translate_cds(seq: &[u8], table: &CodonTable, complete_start: bool) -> String {
+    let mut protein = translate_with(seq, table);
+    if complete_start && seq.len() >= 3 && table.is_start(&seq[..3]) {
+        protein.replace_range(..1, "M");
+    }
+    protein
+}
+
+/// One-letter code of an amino acid named in `/transl_except`, e.g.
+/// `Sec` or `TERM`.
+pub fn amino_acid(name: &str) -> Option<u8> {
+    let letter = match name.to_ascii_lowercase().as_str() {
+        "ala" => b'A',
+        "arg" => b'R',
+        "asn" => b'N',
+        "asp" => b'D',
+        "cys" => b'C',
+        "gln" => b'Q',
+        "glu" => b'E',
+        "gly" => b'G',
+        "his" => b'H',
+        "ile" => b'I',
+        "leu" => b'L',
+        "lys" => b'K',
+        "met" => b'M',
+        "phe" => b'F',
+        "pro" => b'P',
+        "ser" => b'S',
+        "thr" => b'T',
+        "trp" => b'W',
+        "tyr" => b'Y',
+        "val" => b'V',
+        "sec" => b'U',
+        "pyl" => b'O',
+        "asx" => b'B',
+        "glx" => b'Z',
+        "xle" => b'J',
+        "term" => b'*',
+        "other" => b'X',
+        _ => return None,
+    };
+    Some(letter)
+}
+
+/// What to do with stop codons (`*`) in a translated protein.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopPolicy {
+    /// Leave every `*`, including the terminal one.
+    Keep,
+    /// Drop a terminal `*`, leaving internal stops as they are.
+    Strip,
+    /// Cut the protein before its first `*`.
+    Truncate,
+    /// Drop a terminal `*` and replace internal stops with `X`.
+    Mask,
+}
+
+impl StopPolicy {
+    pub fn apply(&self, protein: &str) -> String {
+        match self {
+            StopPolicy::Keep => protein.to_string(),
+            StopPolicy::Strip => protein.strip_suffix('*').unwrap_or(protein).to_string(),
+            StopPolicy::Truncate => protein.split('*').next().unwrap_or_default().to_string(),
+            StopPolicy::Mask => protein
+                .strip_suffix('*')
+                .unwrap_or(protein)
+                .replace('*', "X"),
+        }
+    }
+}
+
+impl FromStr for StopPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(StopPolicy::Keep),
+            "strip" => Ok(StopPolicy::Strip),
+            "truncate" => Ok(StopPolicy::Truncate),
+            "mask" => Ok(StopPolicy::Mask),
+            _ => Err(format!(
+                "unknown stop policy {:?}, expected keep, strip, truncate or mask",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every table in the NCBI `gc.prt`, with stops marked `*` on the
+    /// starts line as NCBI writes them.
+    const NCBI: &[(u8, &str, &str)] = &[
+        (
+            1,
+            "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "---M------**--*----M---------------M----------------------------",
+        ),
+        (
+            2,
+            "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+            "----------**--------------------MMMM----------**---M------------",
+        ),
+        (
+            3,
+            "FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------**----------------------MM---------------M------------",
+        ),
+        (
+            4,
+            "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "--MM------**-------M------------MMMM---------------M------------",
+        ),
+        (
+            5,
+            "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+            "---M------**--------------------MMMM---------------M------------",
+        ),
+        (
+            6,
+            "FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "--------------*--------------------M----------------------------",
+        ),
+        (
+            9,
+            "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+            "----------**-----------------------M---------------M------------",
+        ),
+        (
+            10,
+            "FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------**-----------------------M----------------------------",
+        ),
+        (
+            11,
+            "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "---M------**--*----M------------MMMM---------------M------------",
+        ),
+        (
+            12,
+            "FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------**--*----M---------------M----------------------------",
+        ),
+        (
+            13,
+            "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+            "---M------**----------------------MM---------------M------------",
+        ),
+        (
+            14,
+            "FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+            "-----------*-----------------------M----------------------------",
+        ),
+        (
+            15,
+            "FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------*---*--------------------M----------------------------",
+        ),
+        (
+            16,
+            "FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------*---*--------------------M----------------------------",
+        ),
+        (
+            21,
+            "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+            "----------**-----------------------M---------------M------------",
+        ),
+        (
+            22,
+            "FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "------*---*---*--------------------M----------------------------",
+        ),
+        (
+            23,
+            "FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "--*-------**--*-----------------M--M---------------M------------",
+        ),
+        (
+            24,
+            "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+            "---M------**-------M---------------M---------------M------------",
+        ),
+        (
+            25,
+            "FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "---M------**-----------------------M---------------M------------",
+        ),
+        (
+            26,
+            "FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------**--*----M---------------M----------------------------",
+        ),
+        (
+            27,
+            "FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "--------------*--------------------M----------------------------",
+        ),
+        (
+            28,
+            "FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------**--*--------------------M----------------------------",
+        ),
+        (
+            29,
+            "FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "--------------*--------------------M----------------------------",
+        ),
+        (
+            30,
+            "FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "--------------*--------------------M----------------------------",
+        ),
+        (
+            31,
+            "FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "----------**-----------------------M----------------------------",
+        ),
+        (
+            32,
+            "FFLLSSSSYY*WCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            "---M------*---*----M------------MMMM---------------M------------",
+        ),
+        (
+            33,
+            "FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+            "---M-------*-------M---------------M---------------M------------",
+        ),
+    ];
+
+    fn codon(index: usize) -> [u8; 3] {
+        let base = |shift: usize| b"TCAG"[(index >> shift) & 3];
+        [base(4), base(2), base(0)]
+    }
+
+    #[test]
+    fn every_ncbi_table_matches_gc_prt() {
+        for &(id, amino_acids, starts) in NCBI {
+            let table = CodonTable::new(id).unwrap_or_else(|| panic!("table {} is missing", id));
+            for index in 0..64 {
+                let codon = codon(index);
+                let name = std::str::from_utf8(&codon).unwrap();
+                assert_eq!(
+                    table.codon(&codon) as char,
+                    amino_acids.as_bytes()[index] as char,
+                    "{} in table {}",
+                    name,
+                    id
+                );
+                assert_eq!(
+                    table.is_start(&codon),
+                    starts.as_bytes()[index] == b'M',
+                    "start {} in table {}",
+                    name,
+                    id
+                );
+                // 27, 28 and 31 read their stop codons as sense codons
+                if starts.as_bytes()[index] == b'*' && ![27, 28, 31].contains(&id) {
+                    assert_eq!(table.codon(&codon), b'*', "stop {} in table {}", name, id);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ids_ncbi_does_not_define_are_unknown() {
+        for id in [0, 7, 8, 17, 18, 19, 20, 34, 255] {
+            assert!(CodonTable::new(id).is_none(), "table {}", id);
+        }
+        assert_eq!(TABLES.len(), NCBI.len());
+    }
+
+    #[test]
+    fn ambiguous_codons_resolve_when_they_agree() {
+        let table = CodonTable::default();
+        assert_eq!(table.codon(b"GAY"), b'D');
+        assert_eq!(table.codon(b"GAN"), b'X');
+        assert_eq!(table.codon(b"TAR"), b'*');
+        assert_eq!(table.codon(b"ggn"), b'G');
+        assert_eq!(table.codon(b"A-G"), b'X');
+    }
+
+    #[test]
+    fn alternative_starts_become_methionine() {
+        let bacterial = CodonTable::new(11).unwrap();
+        assert_eq!(translate_cds(b"GTGAAATAA", &bacterial, true), "MK*");
+        assert_eq!(translate_cds(b"GTGAAATAA", &bacterial, false), "VK*");
+        assert_eq!(
+            translate_cds(b"GTGAAATAA", &CodonTable::default(), true),
+            "VK*"
+        );
+        assert_eq!(translate(b"ATGAAATAAG"), "MK*");
+    }
+
+    #[test]
+    fn names_transl_except_amino_acids() {
+        assert_eq!(amino_acid("Sec"), Some(b'U'));
+        assert_eq!(amino_acid("PYL"), Some(b'O'));
+        assert_eq!(amino_acid("TERM"), Some(b'*'));
+        assert_eq!(amino_acid("Foo"), None);
+    }
+
+    #[test]
+    fn stop_policies() {
+        let protein = "MK*P*";
+        assert_eq!(StopPolicy::Keep.apply(protein), "MK*P*");
+        assert_eq!(StopPolicy::Strip.apply(protein), "MK*P");
+        assert_eq!(StopPolicy::Truncate.apply(protein), "MK");
+        assert_eq!(StopPolicy::Mask.apply(protein), "MKXP");
+        assert_eq!(StopPolicy::Mask.apply("MKP"), "MKP");
+        assert_eq!(StopPolicy::Truncate.apply("*M"), "");
+        assert_eq!("mask".parse::<StopPolicy>(), Ok(StopPolicy::Mask));
+        assert_eq!(
+            "drop".parse::<StopPolicy>(),
+            Err("unknown stop policy \"drop\", expected keep, strip, truncate or mask".to_string())
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::record::{Feature, Record};
+use std::fmt;
+use std::io;
+
+/// Column names of the verification TSV.
+pub const HEADER: &str = "record\tid\tstatus\ttranslated_length\tannotated_length\tinternal_stops\tterminal_stop\tmismatches\tfirst_mismatch";
+
+/// Outcome of comparing our translation of a CDS with its `/translation`.
+#[derive(Default, Clone, Debug)]
+pub struct Verification {
+    pub record: String,
+    /// The CDS named as convert names it.
+    pub id: String,
+    pub translated_length: usize,
+    pub annotated_length: usize,
+    pub internal_stops: usize,
+    pub terminal_stop: bool,
+    pub missing_stop: bool,
+    /// The location reaches into another record or between two bases, so
+    /// the CDS was not translated.
+    pub incomplete_location: bool,
+    pub mismatches: usize,
+    /// 1-based position of the first differing residue.
+    pub first_mismatch: Option<usize>,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.issues().is_empty()
+    }
+    /// Short names of every problem found, empty when the translations agree.
+    pub fn issues(&self) -> Vec<&'static str> {
+        let mut issues = Vec::new();
+        if self.incomplete_location {
+            issues.push("incomplete_location");
+            return issues;
+        }
+        if self.mismatches > 0 {
+            issues.push("mismatch");
+        }
+        if self.internal_stops > 0 {
+            issues.push("internal_stop");
+        }
+        if self.translated_length != self.annotated_length {
+            issues.push("length_difference");
+        }
+        if self.missing_stop {
+            issues.push("missing_stop");
+        }
+        issues
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let issues = self.issues();
+        let status = if issues.is_empty() {
+            "ok".to_string()
+        } else {
+            issues.join(",")
+        };
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.record,
+            self.id,
+            status,
+            self.translated_length,
+            self.annotated_length,
+            self.internal_stops,
+            if self.terminal_stop { "yes" } else { "no" },
+            self.mismatches,
+            self.first_mismatch
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        )
+    }
+}
+
+/// Compare the translated CDS with its `/translation` qualifier, reporting
+/// it under `id`. Returns `None` when the feature carries no `/translation`.
+pub fn verify_cds(
+    record: &Record,
+    feature: &Feature,
+    id: String,
+) -> io::Result<Option<Verification>> {
+    let annotated = match feature.qualifier("translation") {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+    if feature.location.incomplete {
+        return Ok(Some(Verification {
+            record: record.id().to_string(),
+            id,
+            annotated_length: annotated.len(),
+            incomplete_location: true,
+            ..Default::default()
+        }));
+    }
+    let protein = feature.translate(record.sequence())?;
+    let terminal_stop = protein.ends_with('*');
+    let body = protein.strip_suffix('*').unwrap_or(&protein);
+    let mut mismatches = 0;
+    let mut first_mismatch = None;
+    for (i, (ours, theirs)) in body.bytes().zip(annotated.bytes()).enumerate() {
+        if ours != theirs {
+            mismatches += 1;
+            first_mismatch.get_or_insert(i + 1);
+        }
+    }
+    Ok(Some(Verification {
+        record: record.id().to_string(),
+        id,
+        translated_length: body.len(),
+        annotated_length: annotated.len(),
+        internal_stops: body.matches('*').count(),
+        terminal_stop,
+        missing_stop: !terminal_stop && !feature.location.three_prime_partial(),
+        incomplete_location: false,
+        mismatches,
+        first_mismatch,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gbk::{self, GbkRead};
+    use crate::location::Location;
+
+    fn record(translations: &[&str]) -> Record {
+        let mut text = String::from(
+            "LOCUS       T1  30 bp    DNA     linear   BCT 01-JAN-2020\nFEATURES             Location/Qualifiers\n",
+        );
+        for translation in translations {
+            text.push_str(&format!(
+                "     CDS             1..15\n                     /translation=\"{}\"\n",
+                translation
+            ));
+        }
+        text.push_str("ORIGIN\n        1 atgaaatgac cctaaggtta gggtcatttc\n//\n");
+        let mut record = Record::new();
+        gbk::Reader::from_bufread(text.as_bytes())
+            .read(&mut record)
+            .unwrap();
+        record
+    }
+
+    #[test]
+    fn reports_each_cds_under_the_id_given() {
+        let record = record(&["MK", "MKWP"]);
+        let rows: Vec<String> = record
+            .cds()
+            .enumerate()
+            .map(|(i, cds)| {
+                verify_cds(&record, cds, format!("T1_feature{}", i + 1))
+                    .unwrap()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            rows[0],
+            "T1\tT1_feature1\tinternal_stop,length_difference\t4\t2\t1\tyes\t0\t-"
+        );
+        assert_eq!(
+            rows[1],
+            "T1\tT1_feature2\tmismatch,internal_stop\t4\t4\t1\tyes\t1\t3"
+        );
+    }
+
+    #[test]
+    fn incomplete_locations_are_not_translated() {
+        let mut record = record(&["MK"]);
+        record.features[0].location = Location::parse("join(1..15,AB000001.1:1..9)").unwrap();
+        let verification = verify_cds(&record, &record.features()[0], "x".to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(verification.issues(), vec!["incomplete_location"]);
+    }
+}